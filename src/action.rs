@@ -0,0 +1,731 @@
+use crate::{
+    bingo::{self, BingoState, BingoVariant},
+    countdown::{self, CountdownFormat, CountdownMode, CountdownTimer, FinishAction},
+    keybindings::HelpOverlay,
+    language,
+    loader::{Config, EbbPaths, LoadDiagnostics, PresetManager},
+    server::{
+        AutoplayResponse, BingoErrorResponse, BingoExhaustedResponse, BingoLogResponse, BulletinResponse, CountdownResponse,
+        ResponseBroadcaster, SoundResponse, WsResponse,
+    },
+    sound::{SoundCue, Sounds},
+    stopwatch::Stopwatch,
+    text_spawner,
+    transition::{self, TransitionEffect},
+    Autoplay, BulletinText, Fonts, ScrollingState, Showing, TextQueue,
+};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::Deserialize;
+
+// 掲示板への操作。キーボード・WebSocket・自動再生などはメッセージを送るだけにして、
+// 実際の処理と WebSocket への通知はここのシステムでまとめて行う
+
+// テキストを表示する。preset・index を省略すると今のプリセットの次のテキスト
+#[derive(Message, Default)]
+pub struct ShowText {
+    pub preset: Option<String>,
+    pub index: Option<usize>,
+    pub transition: Option<TransitionEffect>, // テキストや config.toml の指定より優先する
+}
+
+// ビンゴの次の番号を引く
+#[derive(Message)]
+pub struct BingoNext;
+
+// ビンゴを新しく始める。省略した項目は config.toml の [bingo] のまま
+#[derive(Message, Default)]
+pub struct NewBingoGame {
+    pub variant: Option<BingoVariant>,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub exclude: Option<Vec<u32>>,
+    pub seed: Option<u64>,
+}
+
+// 抽選ログを書き出す
+#[derive(Message)]
+pub struct ExportBingoLog;
+
+// カウントダウンを始める。format・label・on_finish を省略すると config.toml の [countdown]
+#[derive(Message)]
+pub struct StartCountdown {
+    pub seconds: f32,
+    pub mode: CountdownMode,
+    pub format: Option<CountdownFormat>,
+    pub label: Option<String>,
+    pub until: Option<String>, // "15:00" のような終了時刻。指定すると seconds と mode は使わない
+    pub on_finish: Option<FinishAction>,
+}
+
+// 動いているカウントダウンの操作
+#[derive(Message)]
+pub enum CountdownControl {
+    Pause,
+    Resume,
+    TogglePause,
+    Cancel,
+    AddSeconds(Option<f32>), // WebSocket で seconds を省略したときは None（エラーを知らせる）
+}
+
+#[derive(Message)]
+pub enum StopwatchControl {
+    Start { label: Option<String> },
+    Toggle, // 表示していなければ表示して始める
+    Stop,
+    Lap,
+    Reset,
+}
+
+// 自動再生の切り替え。None なら反転する
+#[derive(Message)]
+pub struct SetAutoplay {
+    pub enabled: Option<bool>,
+}
+
+// ミュート・音量の変更。None の項目はそのまま
+#[derive(Message, Default)]
+pub struct SetSound {
+    pub muted: Option<bool>,
+    pub volume: Option<f32>,
+}
+
+// キーなどに割り当てられる操作
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum InputAction {
+    NextText,
+    ToggleAutoplay,
+    ToggleMute,
+    BingoNext,
+    CountdownNormal,      // 通常のカウントダウン（10秒）
+    CountdownAccelerated, // 加速カウントダウン（15秒）
+    CountdownDecelerated, // 減速カウントダウン（15秒）
+    CountdownPause,       // 一時停止・再開の切り替え
+    CountdownCancel,
+    CountdownAddTime,     // 10秒増やす
+    CountdownRemoveTime,  // 10秒減らす
+    StopwatchToggle,      // 表示して開始、または停止・再開
+    StopwatchLap,
+    StopwatchReset,
+    ToggleHelp,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 15] = [
+        InputAction::NextText,
+        InputAction::ToggleAutoplay,
+        InputAction::ToggleMute,
+        InputAction::BingoNext,
+        InputAction::CountdownNormal,
+        InputAction::CountdownAccelerated,
+        InputAction::CountdownDecelerated,
+        InputAction::CountdownPause,
+        InputAction::CountdownCancel,
+        InputAction::CountdownAddTime,
+        InputAction::CountdownRemoveTime,
+        InputAction::StopwatchToggle,
+        InputAction::StopwatchLap,
+        InputAction::StopwatchReset,
+        InputAction::ToggleHelp,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            InputAction::NextText => "next_text",
+            InputAction::ToggleAutoplay => "toggle_autoplay",
+            InputAction::ToggleMute => "toggle_mute",
+            InputAction::BingoNext => "bingo_next",
+            InputAction::CountdownNormal => "countdown_normal",
+            InputAction::CountdownAccelerated => "countdown_accelerated",
+            InputAction::CountdownDecelerated => "countdown_decelerated",
+            InputAction::CountdownPause => "countdown_pause",
+            InputAction::CountdownCancel => "countdown_cancel",
+            InputAction::CountdownAddTime => "countdown_add_time",
+            InputAction::CountdownRemoveTime => "countdown_remove_time",
+            InputAction::StopwatchToggle => "stopwatch_toggle",
+            InputAction::StopwatchLap => "stopwatch_lap",
+            InputAction::StopwatchReset => "stopwatch_reset",
+            InputAction::ToggleHelp => "toggle_help",
+        }
+    }
+
+    // ヘルプに出す説明
+    pub fn description(&self) -> &'static str {
+        match self {
+            InputAction::NextText => "Show the next text",
+            InputAction::ToggleAutoplay => "Autoplay on/off",
+            InputAction::ToggleMute => "Mute/unmute sound",
+            InputAction::BingoNext => "Draw the next bingo number",
+            InputAction::CountdownNormal => "Countdown, 10 s",
+            InputAction::CountdownAccelerated => "Countdown, speeding up",
+            InputAction::CountdownDecelerated => "Countdown, slowing down",
+            InputAction::CountdownPause => "Pause/resume the countdown",
+            InputAction::CountdownCancel => "Cancel the countdown",
+            InputAction::CountdownAddTime => "Countdown +10 s",
+            InputAction::CountdownRemoveTime => "Countdown -10 s",
+            InputAction::StopwatchToggle => "Stopwatch start/stop",
+            InputAction::StopwatchLap => "Stopwatch lap",
+            InputAction::StopwatchReset => "Stopwatch reset",
+            InputAction::ToggleHelp => "Show/hide this help",
+        }
+    }
+}
+
+// 操作のメッセージをまとめて送るためのもの
+#[derive(SystemParam)]
+pub struct ActionWriters<'w> {
+    pub show_text: MessageWriter<'w, ShowText>,
+    pub bingo_next: MessageWriter<'w, BingoNext>,
+    pub new_bingo_game: MessageWriter<'w, NewBingoGame>,
+    pub export_bingo_log: MessageWriter<'w, ExportBingoLog>,
+    pub start_countdown: MessageWriter<'w, StartCountdown>,
+    pub countdown_control: MessageWriter<'w, CountdownControl>,
+    pub stopwatch_control: MessageWriter<'w, StopwatchControl>,
+    pub set_autoplay: MessageWriter<'w, SetAutoplay>,
+    pub set_sound: MessageWriter<'w, SetSound>,
+}
+
+// キーなどの入力を操作のメッセージにして送る
+#[derive(SystemParam)]
+pub struct InputActions<'w> {
+    sounds: Res<'w, Sounds>,
+    help: ResMut<'w, HelpOverlay>,
+    writers: ActionWriters<'w>,
+}
+
+impl InputActions<'_> {
+    pub fn send(&mut self, action: InputAction) {
+        let countdown = |mode, seconds| StartCountdown {
+            seconds,
+            mode,
+            format: None,
+            label: None,
+            until: None,
+            on_finish: None,
+        };
+        match action {
+            InputAction::NextText => {
+                self.writers.show_text.write(ShowText::default());
+            }
+            InputAction::ToggleAutoplay => {
+                self.writers.set_autoplay.write(SetAutoplay { enabled: None });
+            }
+            InputAction::ToggleMute => {
+                self.writers.set_sound.write(SetSound {
+                    muted: Some(!self.sounds.muted),
+                    volume: None,
+                });
+            }
+            InputAction::BingoNext => {
+                self.writers.bingo_next.write(BingoNext);
+            }
+            InputAction::CountdownNormal => {
+                self.writers.start_countdown.write(countdown(CountdownMode::Normal, 10.0));
+            }
+            InputAction::CountdownAccelerated => {
+                self.writers.start_countdown.write(countdown(CountdownMode::Accelerated, 15.0));
+            }
+            InputAction::CountdownDecelerated => {
+                self.writers.start_countdown.write(countdown(CountdownMode::Decelerated, 15.0));
+            }
+            InputAction::CountdownPause => {
+                self.writers.countdown_control.write(CountdownControl::TogglePause);
+            }
+            InputAction::CountdownCancel => {
+                self.writers.countdown_control.write(CountdownControl::Cancel);
+            }
+            InputAction::CountdownAddTime => {
+                self.writers.countdown_control.write(CountdownControl::AddSeconds(Some(10.0)));
+            }
+            InputAction::CountdownRemoveTime => {
+                self.writers.countdown_control.write(CountdownControl::AddSeconds(Some(-10.0)));
+            }
+            InputAction::StopwatchToggle => {
+                self.writers.stopwatch_control.write(StopwatchControl::Toggle);
+            }
+            InputAction::StopwatchLap => {
+                self.writers.stopwatch_control.write(StopwatchControl::Lap);
+            }
+            InputAction::StopwatchReset => {
+                self.writers.stopwatch_control.write(StopwatchControl::Reset);
+            }
+            InputAction::ToggleHelp => self.help.visible = !self.help.visible,
+        }
+    }
+}
+
+// 操作を処理するシステム。入力を受けるシステムはこれより前に置く
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionSet;
+
+pub fn setup_actions(app: &mut App) {
+    app.add_message::<ShowText>()
+        .add_message::<BingoNext>()
+        .add_message::<NewBingoGame>()
+        .add_message::<ExportBingoLog>()
+        .add_message::<StartCountdown>()
+        .add_message::<CountdownControl>()
+        .add_message::<StopwatchControl>()
+        .add_message::<SetAutoplay>()
+        .add_message::<SetSound>()
+        .add_systems(Startup, announce_bingo_game)
+        .add_systems(
+            Update,
+            (
+                show_text_system,
+                bingo_next_system,
+                bingo_game_system,
+                start_countdown_system,
+                countdown_control_system,
+                stopwatch_control_system,
+                settings_system,
+            )
+                .chain()
+                .in_set(ActionSet),
+        );
+}
+
+pub fn broadcast(broadcaster: &Option<Res<ResponseBroadcaster>>, response: WsResponse) {
+    if let Some(broadcaster) = broadcaster {
+        let _ = broadcaster.sender.send(response);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_text_system(
+    mut cmds: Commands,
+    mut messages: MessageReader<ShowText>,
+    mut text_queue: ResMut<TextQueue>,
+    mut scrolling_state: ResMut<ScrollingState>,
+    mut countdown_timer: ResMut<CountdownTimer>,
+    sounds: Res<Sounds>,
+    preset_manager: Res<PresetManager>,
+    fonts: Res<Fonts>,
+    config: Res<Config>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+    text_query: Query<Entity, With<Showing>>,
+) {
+    for ShowText { preset, index, transition } in messages.read() {
+        // プリセットが指定されていて、現在のプリセットと異なる場合は切り替え
+        if let Some(preset) = preset
+            && text_queue.current_preset != *preset
+        {
+            match preset_manager.presets.get(preset) {
+                Some(texts) => {
+                    text_queue.switch_preset(preset, texts.clone(), preset_manager.meta(preset).language_mode);
+                    println!("Switched to preset: {}", preset);
+                }
+                None => println!("Preset '{}' not found, using current preset '{}'", preset, text_queue.current_preset),
+            }
+        }
+        if let Some(index) = *index {
+            if index >= text_queue.texts.len() {
+                println!("Text index {} not found in preset '{}'", index, text_queue.current_preset);
+                continue;
+            }
+            text_queue.current_index = index;
+            text_queue.language_step = 0;
+        }
+        text_queue.wrap();
+
+        countdown_timer.stop();
+
+        // 効果は操作の指定 → テキストの指定 → config.toml の順で決める
+        let source_transition = text_queue.texts.get(text_queue.current_index).and_then(|source| source.transition);
+        let transition = config.transition.with_effect(transition.or(source_transition));
+        transition::leave_all(&mut cmds, text_query.iter(), &transition);
+
+        let shown = text_queue.current_index;
+        let Some(source) = text_queue.texts.get(shown).cloned() else {
+            continue;
+        };
+        let languages = text_queue.languages(shown);
+        let segments = fonts.segments(&source, &languages);
+        let now_text: String = segments.iter().map(|segment| segment.text.as_str()).collect();
+        if source.chime == Some(true) {
+            sounds.play(&mut cmds, SoundCue::Chime);
+        }
+
+        if source.duration == 0.0 {
+            text_spawner::spawn_static_text(&mut cmds, segments, &transition).insert(BulletinText);
+        } else {
+            text_spawner::spawn_text(&mut cmds, segments, source.duration, &transition).insert(BulletinText);
+        }
+
+        text_queue.advance();
+        scrolling_state.is_active = true;
+
+        let content = |index: Option<usize>| {
+            index.and_then(|index| text_queue.texts.get(index))
+                .map(|text| text.content.clone())
+                .unwrap_or_default()
+        };
+        broadcast(&broadcaster, WsResponse::Bulletin(BulletinResponse {
+            prev_text: content(shown.checked_sub(1)),
+            now_text,
+            next_text: content(Some(shown + 1)),
+            language: language::language_label(&languages),
+        }));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn bingo_next_system(
+    mut cmds: Commands,
+    mut messages: MessageReader<BingoNext>,
+    mut bingo_state: ResMut<BingoState>,
+    mut countdown_timer: ResMut<CountdownTimer>,
+    fonts: Res<Fonts>,
+    config: Res<Config>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+    text_query: Query<Entity, With<Showing>>,
+) {
+    for _ in messages.read() {
+        transition::leave_all(&mut cmds, text_query.iter(), &config.transition);
+        countdown_timer.stop();
+
+        // 結果はルーレットが止まったときに finish_roulette から送る
+        match bingo_state.next() {
+            Some(number) => bingo::spawn_roulette(&mut cmds, &fonts, &bingo_state, number),
+            None => {
+                text_spawner::spawn_static_text(&mut cmds, fonts.plain("0"), &config.transition);
+                println!("Bingo: no numbers left");
+                broadcast(&broadcaster, WsResponse::BingoExhausted(BingoExhaustedResponse {
+                    drawn: bingo_state.log.len(),
+                }));
+            }
+        }
+    }
+}
+
+// 起動時のゲームの commitment も WebSocket で公開する
+fn announce_bingo_game(bingo_state: Res<BingoState>, broadcaster: Option<Res<ResponseBroadcaster>>) {
+    if let Some(broadcaster) = &broadcaster {
+        broadcaster.announce_bingo_game(bingo_state.game_response());
+    }
+}
+
+fn bingo_game_system(
+    mut new_games: MessageReader<NewBingoGame>,
+    mut exports: MessageReader<ExportBingoLog>,
+    mut bingo_state: ResMut<BingoState>,
+    mut diagnostics: ResMut<LoadDiagnostics>,
+    config: Res<Config>,
+    paths: Res<EbbPaths>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+) {
+    let log_dir = paths.config_dir.join("bingo_logs");
+    for request in new_games.read() {
+        let mut game = config.bingo.clone();
+        game.variant = request.variant.unwrap_or(game.variant);
+        game.min = request.min.unwrap_or(game.min);
+        game.max = request.max.unwrap_or(game.max);
+        game.exclude = request.exclude.clone().unwrap_or(game.exclude);
+        game.seed = request.seed.or(game.seed);
+        if let Err(error) = bingo::check_range(&game) {
+            println!("Err: {}", error);
+            broadcast(&broadcaster, WsResponse::BingoError(BingoErrorResponse { error }));
+            continue;
+        }
+
+        // 引いた記録が消えないよう、前のゲームのログを書き出しておく
+        if !bingo_state.log.is_empty() {
+            match bingo_state.export_log(&log_dir) {
+                Ok(path) => println!("Exported bingo log to {:?}", path),
+                Err(e) => println!("Err: Can't export bingo log: {}", e),
+            }
+        }
+
+        // 抽選の名前はファイルを読み直す
+        *bingo_state = bingo::new_game(&paths, &game, &mut diagnostics);
+        println!("New bingo game: {} ({} numbers)", game.variant.code(), bingo_state.numbers.len());
+
+        if let Some(broadcaster) = &broadcaster {
+            broadcaster.announce_bingo_game(bingo_state.game_response());
+        }
+    }
+    for _ in exports.read() {
+        match bingo_state.export_log(&log_dir) {
+            Ok(path) => {
+                println!("Exported bingo log to {:?}", path);
+                broadcast(&broadcaster, WsResponse::BingoLog(BingoLogResponse {
+                    path: path.display().to_string(),
+                }));
+            }
+            Err(e) => println!("Err: Can't export bingo log: {}", e),
+        }
+    }
+}
+
+fn start_countdown_system(
+    mut cmds: Commands,
+    mut messages: MessageReader<StartCountdown>,
+    mut countdown_timer: ResMut<CountdownTimer>,
+    config: Res<Config>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+    text_query: Query<Entity, With<Showing>>,
+) {
+    for request in messages.read() {
+        // 時刻指定で過ぎた時刻なら翌日のその時刻まで
+        let target = match &request.until {
+            Some(until) => match countdown::parse_target(until, true) {
+                Some(target) => Some(target),
+                None => {
+                    broadcast(&broadcaster, WsResponse::Countdown(CountdownResponse {
+                        status: format!("invalid time: {}", until),
+                    }));
+                    continue;
+                }
+            },
+            None if !CountdownTimer::is_valid_seconds(request.seconds) => {
+                broadcast(&broadcaster, WsResponse::Countdown(CountdownResponse {
+                    status: format!("invalid seconds: {} (must be more than 0 and at most {})", request.seconds, countdown::MAX_SECONDS),
+                }));
+                continue;
+            }
+            None => None,
+        };
+
+        // 他のテキストを片付ける
+        transition::leave_all(&mut cmds, text_query.iter(), &config.transition);
+
+        countdown_timer.format = request.format.unwrap_or(config.countdown.format);
+        countdown_timer.label = request.label.clone().or_else(|| config.countdown.label.clone());
+        countdown_timer.on_finish = request.on_finish.clone().unwrap_or_else(|| config.countdown.on_finish.clone());
+
+        let status = match target {
+            Some(target) => {
+                countdown_timer.start_until(target);
+                format!(
+                    "started until {} ({:.0}s)",
+                    request.until.as_deref().unwrap_or_default(),
+                    countdown_timer.remaining_seconds()
+                )
+            }
+            None => {
+                countdown_timer.mode = request.mode;
+                countdown_timer.initial_seconds = request.seconds;
+                countdown_timer.timer = Timer::from_seconds(request.seconds, TimerMode::Once);
+                countdown_timer.start();
+                format!("started {} mode ({}s)", request.mode.code(), request.seconds)
+            }
+        };
+        broadcast(&broadcaster, WsResponse::Countdown(CountdownResponse { status }));
+    }
+}
+
+fn countdown_control_system(
+    mut messages: MessageReader<CountdownControl>,
+    mut countdown_timer: ResMut<CountdownTimer>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+) {
+    for control in messages.read() {
+        let status = match control {
+            CountdownControl::Pause => countdown_timer.pause().then(|| "paused".to_string()),
+            CountdownControl::Resume => countdown_timer.resume().then(|| "resumed".to_string()),
+            CountdownControl::TogglePause => {
+                if countdown_timer.pause() {
+                    Some("paused".to_string())
+                } else {
+                    countdown_timer.resume().then(|| "resumed".to_string())
+                }
+            }
+            CountdownControl::AddSeconds(None) => Some("`seconds` is required for add_seconds".to_string()),
+            CountdownControl::AddSeconds(Some(seconds)) if !seconds.is_finite() => Some(format!("invalid seconds: {}", seconds)),
+            CountdownControl::AddSeconds(_) if countdown_timer.is_active && !countdown_timer.is_adjustable() => {
+                Some(format!("not adjusted ({} mode has a fixed length)", countdown_timer.mode.code()))
+            }
+            CountdownControl::AddSeconds(Some(seconds)) => {
+                // 上限・下限で切り詰めたときは実際に動いた分を知らせる
+                countdown_timer.add_seconds(*seconds).map(|applied| {
+                    if applied == 0.0 {
+                        "not adjusted (limit reached)".to_string()
+                    } else {
+                        format!("adjusted ({:+}s)", (applied * 10.0).round() / 10.0)
+                    }
+                })
+            }
+            CountdownControl::Cancel => {
+                // "cancelled" は countdown_finished_system から送る
+                if countdown_timer.cancel() {
+                    continue;
+                }
+                None
+            }
+        };
+        let status = status.unwrap_or_else(|| "not running".to_string());
+        broadcast(&broadcaster, WsResponse::Countdown(CountdownResponse { status }));
+    }
+}
+
+fn stopwatch_control_system(
+    mut cmds: Commands,
+    mut messages: MessageReader<StopwatchControl>,
+    mut stopwatch: ResMut<Stopwatch>,
+    mut countdown_timer: ResMut<CountdownTimer>,
+    config: Res<Config>,
+    text_query: Query<Entity, With<Showing>>,
+) {
+    // 応答は stopwatch_system からまとめて送る
+    for control in messages.read() {
+        match control {
+            StopwatchControl::Start { .. } | StopwatchControl::Toggle if !stopwatch.shown => {
+                // 他のテキストを片付けてから表示する
+                transition::leave_all(&mut cmds, text_query.iter(), &config.transition);
+                countdown_timer.stop();
+                if let StopwatchControl::Start { label: Some(label) } = control {
+                    stopwatch.label = Some(label.clone());
+                }
+                stopwatch.start();
+            }
+            StopwatchControl::Start { label } => {
+                if let Some(label) = label {
+                    stopwatch.label = Some(label.clone());
+                }
+                stopwatch.start();
+            }
+            StopwatchControl::Toggle => stopwatch.toggle(),
+            StopwatchControl::Stop => stopwatch.stop(),
+            StopwatchControl::Lap => stopwatch.lap(),
+            StopwatchControl::Reset => stopwatch.reset(),
+        }
+    }
+}
+
+fn settings_system(
+    mut autoplay_messages: MessageReader<SetAutoplay>,
+    mut sound_messages: MessageReader<SetSound>,
+    mut autoplay: ResMut<Autoplay>,
+    mut sounds: ResMut<Sounds>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+) {
+    for SetAutoplay { enabled } in autoplay_messages.read() {
+        autoplay.active = enabled.unwrap_or(!autoplay.active);
+        println!("Autoplay: {}", if autoplay.active { "on" } else { "off" });
+        broadcast(&broadcaster, WsResponse::Autoplay(AutoplayResponse {
+            enabled: autoplay.active,
+        }));
+    }
+    for SetSound { muted, volume } in sound_messages.read() {
+        if let Some(muted) = muted {
+            sounds.muted = *muted;
+        }
+        if let Some(volume) = volume
+            && !sounds.set_volume(*volume)
+        {
+            println!("Err: Invalid volume: {}", volume);
+        }
+        println!("Sound: {} (volume {:.2})", if sounds.muted { "muted" } else { "on" }, sounds.volume);
+        broadcast(&broadcaster, WsResponse::Sound(SoundResponse {
+            muted: sounds.muted,
+            volume: sounds.volume,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gamepad::{self, GamepadBindings},
+        keybindings::KeyBindings,
+    };
+
+    // 入力のシステムだけを動かす App。keybindings は [keybindings] に書く内容
+    fn input_app(keybindings: &str) -> App {
+        let paths = EbbPaths::new(Some("/nonexistent".into()), None);
+        let config = Config {
+            keybindings: toml::from_str(keybindings).unwrap(),
+            ..default()
+        };
+        let mut diagnostics = LoadDiagnostics::default();
+
+        let mut app = App::new();
+        app.add_message::<ShowText>()
+            .add_message::<BingoNext>()
+            .add_message::<NewBingoGame>()
+            .add_message::<ExportBingoLog>()
+            .add_message::<StartCountdown>()
+            .add_message::<CountdownControl>()
+            .add_message::<StopwatchControl>()
+            .add_message::<SetAutoplay>()
+            .add_message::<SetSound>()
+            .init_resource::<Sounds>()
+            .init_resource::<HelpOverlay>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .insert_resource(KeyBindings::load(&paths, &config, &mut diagnostics))
+            .insert_resource(GamepadBindings::load(&paths, &config, &mut diagnostics))
+            .add_systems(Update, (crate::handle_keyboard_action, gamepad::gamepad_input_system));
+        assert!(diagnostics.errors.is_empty());
+        app
+    }
+
+    fn press_keys(app: &mut App, keys: &[KeyCode]) {
+        let mut input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        input.reset_all();
+        for key in keys {
+            input.press(*key);
+        }
+        app.update();
+    }
+
+    fn press_button(app: &mut App, button: GamepadButton) {
+        let mut gamepad = Gamepad::default();
+        gamepad.digital_mut().press(button);
+        app.world_mut().spawn(gamepad);
+        app.update();
+    }
+
+    fn sent<M: Message>(app: &App) -> Vec<&M> {
+        app.world().resource::<Messages<M>>().iter_current_update_messages().collect()
+    }
+
+    fn countdown_modes(app: &App) -> Vec<(CountdownMode, f32)> {
+        sent::<StartCountdown>(app).iter().map(|request| (request.mode, request.seconds)).collect()
+    }
+
+    #[test]
+    fn default_keys_send_actions() {
+        let mut app = input_app("");
+        press_keys(&mut app, &[KeyCode::Enter]);
+        assert_eq!(sent::<ShowText>(&app).len(), 1);
+        assert!(sent::<ShowText>(&app)[0].preset.is_none());
+
+        press_keys(&mut app, &[KeyCode::KeyC]);
+        assert_eq!(countdown_modes(&app), vec![(CountdownMode::Normal, 10.0)]);
+        assert!(sent::<ShowText>(&app).is_empty());
+
+        press_keys(&mut app, &[KeyCode::F1]);
+        assert!(app.world().resource::<HelpOverlay>().visible);
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly() {
+        let mut app = input_app("countdown_accelerated = \"Ctrl+C\"");
+        press_keys(&mut app, &[KeyCode::ControlLeft, KeyCode::KeyC]);
+        assert_eq!(countdown_modes(&app), vec![(CountdownMode::Accelerated, 15.0)]);
+
+        press_keys(&mut app, &[KeyCode::ShiftLeft, KeyCode::KeyC]);
+        assert!(countdown_modes(&app).is_empty());
+    }
+
+    #[test]
+    fn modifier_key_can_be_bound_alone() {
+        let mut app = input_app("bingo_next = \"ShiftLeft\"\nnext_text = []");
+        press_keys(&mut app, &[KeyCode::ShiftLeft]);
+        assert_eq!(sent::<BingoNext>(&app).len(), 1);
+
+        press_keys(&mut app, &[KeyCode::Enter]);
+        assert!(sent::<ShowText>(&app).is_empty());
+    }
+
+    #[test]
+    fn gamepad_buttons_send_actions() {
+        let mut app = input_app("");
+        press_button(&mut app, GamepadButton::East);
+        assert_eq!(sent::<BingoNext>(&app).len(), 1);
+
+        press_button(&mut app, GamepadButton::West);
+        assert_eq!(countdown_modes(&app), vec![(CountdownMode::Normal, 10.0)]);
+    }
+}
@@ -1,31 +1,537 @@
+use crate::{
+    loader::{self, BingoConfig, Config, EbbPaths, LoadDiagnostics, LoadError, TransitionConfig},
+    server::{BingoGameResponse, BingoResponse, ResponseBroadcaster, WsResponse},
+    sound::{SoundCue, Sounds},
+    text_spawner,
+    transition::FadeComponent,
+    Fonts, Showing,
+};
 use bevy::prelude::*;
-use rand::seq::SliceRandom;
+use rand::{
+    seq::{IndexedRandom, SliceRandom},
+    Rng, SeedableRng,
+};
+use rand_chacha::ChaCha20Rng;
+use serde::{
+    de::{self, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+// 抽選の種類
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BingoVariant {
+    #[default]
+    Ball75,  // 1-75、B/I/N/G/O 付き
+    Ball90,  // 1-90
+    Range,   // min から max まで（整理券番号の抽選など）
+    Lottery, // lottery_file の名前から引く
+}
+
+impl BingoVariant {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BingoVariant::Ball75 => "ball75",
+            BingoVariant::Ball90 => "ball90",
+            BingoVariant::Range => "range",
+            BingoVariant::Lottery => "lottery",
+        }
+    }
+}
+
+// range で引ける番号の数の上限（全部の番号を並べておくので、大きすぎる範囲はメモリを使い切る）
+pub const MAX_NUMBERS: u64 = 100_000;
+
+// variant = "range" の min から max までが MAX_NUMBERS を超えていないか
+pub fn check_range(config: &BingoConfig) -> Result<(), String> {
+    if config.variant != BingoVariant::Range || config.max < config.min {
+        return Ok(());
+    }
+    let count = config.max as u64 - config.min as u64 + 1;
+    if count > MAX_NUMBERS {
+        return Err(format!(
+            "`[bingo]` range {}-{} has {} numbers, at most {} are allowed",
+            config.min, config.max, count, MAX_NUMBERS,
+        ));
+    }
+    Ok(())
+}
+
+// 引く順に並べた番号。Lottery では names の何番目か（1始まり）を番号として扱う
 #[derive(Resource, Default)]
 pub struct BingoState {
-    pub numbers: Vec<u8>,
+    pub numbers: Vec<u32>,
     pub index: usize,
+    pub variant: BingoVariant,
+    pub names: Vec<String>,
+    pub seed: u64,
+    pub nonce: String,      // commitment に混ぜる秘密の値。ログを書き出すまで明かさない
+    pub commitment: String, // nonce と引く順全体のハッシュ。開始時に公開しておけば後から検証できる
+    pub started_at: u64,    // UNIX 時刻（ミリ秒）
+    pub log: Vec<DrawRecord>,
+}
+
+// 1回分の抽選結果
+#[derive(Serialize, Debug, Clone)]
+pub struct DrawRecord {
+    pub no: usize,
+    pub number: u32,
+    pub label: String,
+    pub drawn_at: u64, // UNIX 時刻（ミリ秒）
+}
+
+// 引く順を決める方法。seed から順番を再現するときに同じものを使う
+const SHUFFLE: &str = "rand 0.9 SliceRandom::shuffle with rand_chacha 0.9 ChaCha20Rng::seed_from_u64";
+
+// 書き出す抽選ログ。nonce と order から commitment を計算し直せば検証できる。
+// seed は 2^53 を超えると JavaScript などの数値では丸められてしまうので10進の文字列で書く
+#[derive(Serialize)]
+struct DrawLog<'a> {
+    variant: &'static str,
+    shuffle: &'static str,
+    seed: String,
+    nonce: &'a str,
+    commitment: &'a str,
+    started_at: u64,
+    order: Vec<String>,
+    draws: &'a [DrawRecord],
 }
 
 impl BingoState {
-    pub fn new() -> Self {
-        let mut rng = rand::rng();
-        let mut n: Vec<u8> = (1..=75).collect();
+    // seed が同じなら（同じバージョンのこのソフトでは）同じ順番になる
+    pub fn new(config: &BingoConfig, names: Vec<String>) -> Self {
+        let seed = config.seed.unwrap_or_else(|| rand::rng().random());
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let range = match config.variant {
+            BingoVariant::Ball75 => 1..=75,
+            BingoVariant::Ball90 => 1..=90,
+            BingoVariant::Range => config.min..=config.max,
+            BingoVariant::Lottery => 1..=names.len() as u32,
+        };
+        // 大きすぎる範囲は並べない（new_game と WebSocket の new_game がエラーを報告する）
+        let mut n: Vec<u32> = match check_range(config) {
+            Ok(()) => range.filter(|number| !config.exclude.contains(number)).collect(),
+            Err(_) => Vec::new(),
+        };
         n.shuffle(&mut rng);
 
-        Self {
+        let mut state = Self {
             numbers: n,
             index: 0,
+            variant: config.variant,
+            names,
+            seed,
+            nonce: random_nonce(),
+            commitment: String::new(),
+            started_at: unix_millis(),
+            log: Vec::new(),
+        };
+        state.commitment = commitment(&state.nonce, &state.order());
+        println!("Bingo game started: {} numbers, commitment {}", state.numbers.len(), state.commitment);
+        state
+    }
+
+    // 開始を知らせる WebSocket のレスポンス（seed と nonce は含めない）
+    pub fn game_response(&self) -> BingoGameResponse {
+        BingoGameResponse {
+            variant: self.variant.code().to_string(),
+            count: self.numbers.len(),
+            commitment: self.commitment.clone(),
         }
     }
 
-    pub fn next(&mut self) -> Option<u8> {
+    // 引く順に並べた表示用の文字列
+    fn order(&self) -> Vec<String> {
+        self.numbers.iter().map(|number| self.label(*number)).collect()
+    }
+
+    pub fn next(&mut self) -> Option<u32> {
         if self.index < self.numbers.len() {
             let num = self.numbers[self.index];
             self.index += 1;
+            self.log.push(DrawRecord {
+                no: self.index,
+                number: num,
+                label: self.label(num),
+                drawn_at: unix_millis(),
+            });
             Some(num)
         } else {
             None
         }
     }
+
+    // まだ引いていない番号
+    pub fn remaining(&self) -> &[u32] {
+        &self.numbers[self.index.min(self.numbers.len())..]
+    }
+
+    // 画面に出す文字列（75ボールは列の文字付き、抽選は名前）
+    pub fn label(&self, number: u32) -> String {
+        match self.variant {
+            BingoVariant::Ball75 => format!("{} {}", letter(number), number),
+            BingoVariant::Ball90 | BingoVariant::Range => number.to_string(),
+            BingoVariant::Lottery => number.checked_sub(1)
+                .and_then(|index| self.names.get(index as usize))
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    // 抽選ログを JSON で書き出す。seed も含むので、引き終わってから公開する
+    pub fn export_log(&self, dir: &Path) -> std::io::Result<PathBuf> {
+        let log = DrawLog {
+            variant: self.variant.code(),
+            shuffle: SHUFFLE,
+            seed: self.seed.to_string(),
+            nonce: &self.nonce,
+            commitment: &self.commitment,
+            started_at: self.started_at,
+            order: self.order(),
+            draws: &self.log,
+        };
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("bingo-{}.json", self.started_at));
+        std::fs::write(&path, serde_json::to_string_pretty(&log)? + "\n")?;
+        Ok(path)
+    }
+}
+
+// nonce の16進文字列と、1行に1つずつ並べた引く順をつなげたもののハッシュ。
+// nonce が分からないうちは、番号の並びを総当たりしてもハッシュと突き合わせられない
+fn commitment(nonce: &str, order: &[String]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(nonce.as_bytes());
+    for label in order {
+        hasher.update(b"\n");
+        hasher.update(label.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+// seed は数値のほか10進の文字列でも書ける（ログの seed をそのまま貼り付けられるように）
+pub fn deserialize_seed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    struct SeedVisitor;
+
+    impl<'de> Visitor<'de> for SeedVisitor {
+        type Value = Option<u64>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a seed from 0 to 18446744073709551615, as a number or a decimal string")
+        }
+
+        fn visit_u64<E: de::Error>(self, seed: u64) -> Result<Self::Value, E> {
+            Ok(Some(seed))
+        }
+
+        fn visit_i64<E: de::Error>(self, seed: i64) -> Result<Self::Value, E> {
+            u64::try_from(seed).map(Some).map_err(|_| E::invalid_value(Unexpected::Signed(seed), &self))
+        }
+
+        fn visit_str<E: de::Error>(self, seed: &str) -> Result<Self::Value, E> {
+            seed.trim().parse().map(Some).map_err(|_| E::invalid_value(Unexpected::Str(seed), &self))
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    deserializer.deserialize_any(SeedVisitor)
+}
+
+fn random_nonce() -> String {
+    let bytes: [u8; 16] = rand::rng().random();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// 番号の列の文字（1-15: B, 16-30: I, 31-45: N, 46-60: G, 61-75: O）
+pub fn letter(number: u32) -> char {
+    match number {
+        0..=15 => 'B',
+        16..=30 => 'I',
+        31..=45 => 'N',
+        46..=60 => 'G',
+        _ => 'O',
+    }
+}
+
+// 抽選の設定と名前の一覧から新しいゲームを作る。名前が読めなければエラーを記録して空にする
+pub fn new_game(paths: &EbbPaths, config: &BingoConfig, diagnostics: &mut LoadDiagnostics) -> BingoState {
+    let names = match (&config.variant, &config.lottery_file) {
+        (BingoVariant::Lottery, Some(file)) => {
+            loader::load_lottery(&paths.config_dir.join(file)).unwrap_or_else(|e| {
+                diagnostics.push(e);
+                Vec::new()
+            })
+        }
+        (BingoVariant::Lottery, None) => {
+            diagnostics.push(LoadError::new(&paths.config_file(), None, None, "`bingo.lottery_file` is required for the lottery variant"));
+            Vec::new()
+        }
+        _ => Vec::new(),
+    };
+    let state = BingoState::new(config, names);
+    if let Err(e) = check_range(config) {
+        diagnostics.push(LoadError::new(&paths.config_file(), None, None, e));
+    } else if state.numbers.is_empty() && config.variant != BingoVariant::Lottery {
+        diagnostics.push(LoadError::new(&paths.config_file(), None, None, "`[bingo]` leaves no numbers to draw"));
+    }
+    state
+}
+
+// ルーレットで回す候補の数（抽選の名前が多くても毎回全部は作らない）
+const ROULETTE_CANDIDATES: usize = 50;
+
+// 抽選演出中のテキスト。表示を切り替えながら減速し、number で止まる
+#[derive(Component)]
+pub struct BingoRoulette {
+    number: u32,
+    label: String,
+    no: usize,
+    candidates: Vec<String>,
+    elapsed: f32,
+    next_change: f32,
+    drumroll: Option<Entity>, // 回っている間のドラムロール。ルーレットが終わったら止める
+}
+
+// 引いた番号のルーレットを表示する。止まったときに WebSocket で結果を知らせる
+pub fn spawn_roulette(cmds: &mut Commands, fonts: &Fonts, bingo: &BingoState, number: u32) {
+    // 止まる番号もルーレットの候補に入れる
+    let mut rng = rand::rng();
+    let mut candidates: Vec<String> = bingo.remaining()
+        .choose_multiple(&mut rng, ROULETTE_CANDIDATES)
+        .map(|number| bingo.label(*number))
+        .collect();
+    let label = bingo.label(number);
+    candidates.push(label.clone());
+    let first = candidates.choose(&mut rng).cloned().unwrap_or_else(|| label.clone());
+
+    text_spawner::spawn_static_text(cmds, fonts.plain(&first), &TransitionConfig::default())
+        .insert(BingoRoulette {
+            number,
+            label,
+            no: bingo.index,
+            candidates,
+            elapsed: 0.0,
+            next_change: 0.0,
+            drumroll: None,
+        });
+}
+
+// 回っている途中で別の表示に押し出されたルーレットは、最後まで回さずにその場で止める
+pub fn roulette_system(
+    mut cmds: Commands,
+    time: Res<Time>,
+    config: Res<Config>,
+    fonts: Res<Fonts>,
+    sounds: Res<Sounds>,
+    mut query: Query<(Entity, &mut BingoRoulette, Has<Showing>)>,
+    mut writer: Text2dWriter,
+) {
+    let spin = &config.bingo;
+    for (entity, mut roulette, showing) in &mut query {
+        if !showing {
+            cmds.entity(entity).remove::<BingoRoulette>();
+            continue;
+        }
+        if roulette.elapsed == 0.0 {
+            roulette.drumroll = sounds.play(&mut cmds, SoundCue::Drumroll);
+        }
+        roulette.elapsed += time.delta_secs();
+
+        if roulette.elapsed >= spin.spin_duration {
+            // 止まった番号をフェードインで見せる。結果は finish_roulette が知らせる
+            text_spawner::replace_segments(&mut cmds, &mut writer, entity, fonts.plain(&roulette.label));
+            cmds.entity(entity)
+                .remove::<BingoRoulette>()
+                .insert(FadeComponent::fade_in(spin.reveal_fade));
+            sounds.play(&mut cmds, SoundCue::Reveal);
+            continue;
+        }
+
+        if roulette.elapsed >= roulette.next_change {
+            // 切り替え間隔を start_interval から end_interval まで二次的に伸ばして減速させる
+            let progress = roulette.elapsed / spin.spin_duration;
+            let interval = spin.start_interval + (spin.end_interval - spin.start_interval) * progress * progress;
+            roulette.next_change = roulette.elapsed + interval;
+
+            if let Some(label) = roulette.candidates.choose(&mut rand::rng()) {
+                text_spawner::replace_segments(&mut cmds, &mut writer, entity, fonts.plain(label));
+            }
+        }
+    }
+}
+
+// ルーレットが終わったら（止まったとき・途中で片付けられたとき・消されたときのどれでも）
+// ドラムロールを止めて、引いた番号を WebSocket で知らせる。番号は引いた時点で決まっているので取りこぼさない
+pub fn finish_roulette(
+    trigger: On<Remove, BingoRoulette>,
+    mut cmds: Commands,
+    query: Query<&BingoRoulette>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+) {
+    let Ok(roulette) = query.get(trigger.entity) else {
+        return;
+    };
+    if let Some(drumroll) = roulette.drumroll {
+        cmds.entity(drumroll).try_despawn();
+    }
+    println!("Bingo: {} (No.{})", roulette.label, roulette.no);
+
+    if let Some(broadcaster) = &broadcaster {
+        let response = WsResponse::Bingo(BingoResponse {
+            current: roulette.number,
+            no: roulette.no,
+            label: roulette.label.clone(),
+        });
+        let _ = broadcaster.sender.send(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(variant: BingoVariant, seed: u64) -> BingoConfig {
+        BingoConfig {
+            variant,
+            seed: Some(seed),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn excluded_numbers_are_never_drawn() {
+        let game = BingoConfig {
+            min: 10,
+            max: 20,
+            exclude: vec![12, 15, 99],
+            ..config(BingoVariant::Range, 1)
+        };
+        let mut state = BingoState::new(&game, Vec::new());
+        assert_eq!(state.numbers.len(), 9);
+
+        let mut drawn = Vec::new();
+        while let Some(number) = state.next() {
+            drawn.push(number);
+        }
+        assert!(drawn.iter().all(|number| (10..=20).contains(number) && *number != 12 && *number != 15));
+        assert_eq!(state.log.len(), 9);
+        assert!(state.remaining().is_empty());
+    }
+
+    #[test]
+    fn labels_follow_variant() {
+        assert_eq!(letter(1), 'B');
+        assert_eq!(letter(31), 'N');
+        assert_eq!(letter(75), 'O');
+
+        let state = BingoState::new(&config(BingoVariant::Ball75, 1), Vec::new());
+        assert_eq!(state.label(7), "B 7");
+        assert_eq!(state.label(64), "O 64");
+
+        let names = vec!["Alice".to_string(), "Bob".to_string()];
+        let lottery = BingoState::new(&config(BingoVariant::Lottery, 1), names);
+        assert_eq!(lottery.label(2), "Bob");
+        assert_eq!(lottery.numbers.len(), 2);
+        assert_eq!(lottery.label(0), "");
+        assert_eq!(lottery.label(3), "");
+    }
+
+    #[test]
+    fn log_writes_seed_as_string() {
+        let state = BingoState::new(&config(BingoVariant::Ball75, u64::MAX), Vec::new());
+        let dir = std::env::temp_dir().join(format!("ebb-test-bingo-log-{}", std::process::id()));
+        let path = state.export_log(&dir).unwrap();
+        let log: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(log["seed"], "18446744073709551615");
+    }
+
+    #[test]
+    fn seed_accepts_number_or_string() {
+        #[derive(Deserialize)]
+        struct Game {
+            #[serde(default, deserialize_with = "deserialize_seed")]
+            seed: Option<u64>,
+        }
+        let seed = |json: &str| serde_json::from_str::<Game>(json).map(|game| game.seed);
+        assert_eq!(seed(r#"{"seed": 42}"#).unwrap(), Some(42));
+        assert_eq!(seed(r#"{"seed": "18446744073709551615"}"#).unwrap(), Some(u64::MAX));
+        assert_eq!(seed(r#"{"seed": null}"#).unwrap(), None);
+        assert_eq!(seed("{}").unwrap(), None);
+        assert!(seed(r#"{"seed": -1}"#).is_err());
+        assert!(seed(r#"{"seed": "18446744073709551616"}"#).is_err());
+        assert!(seed(r#"{"seed": 1.5}"#).is_err());
+
+        let config: BingoConfig = toml::from_str("seed = \"18446744073709551615\"").unwrap();
+        assert_eq!(config.seed, Some(u64::MAX));
+    }
+
+    #[test]
+    fn huge_ranges_are_rejected() {
+        let game = BingoConfig {
+            min: 1,
+            max: 4_000_000_000,
+            ..config(BingoVariant::Range, 1)
+        };
+        assert!(check_range(&game).is_err());
+        assert!(BingoState::new(&game, Vec::new()).numbers.is_empty());
+
+        let game = BingoConfig {
+            min: 1,
+            max: MAX_NUMBERS as u32,
+            ..config(BingoVariant::Range, 1)
+        };
+        assert!(check_range(&game).is_ok());
+        assert_eq!(BingoState::new(&game, Vec::new()).numbers.len(), MAX_NUMBERS as usize);
+    }
+
+    #[test]
+    fn same_seed_gives_same_order() {
+        let a = BingoState::new(&config(BingoVariant::Ball75, 42), Vec::new());
+        let b = BingoState::new(&config(BingoVariant::Ball75, 42), Vec::new());
+        let c = BingoState::new(&config(BingoVariant::Ball75, 43), Vec::new());
+        assert_eq!(a.numbers, b.numbers);
+        assert_ne!(a.numbers, c.numbers);
+
+        let mut sorted = a.numbers.clone();
+        sorted.sort();
+        assert_eq!(sorted, (1..=75).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn commitment_covers_nonce_and_order() {
+        let a = BingoState::new(&config(BingoVariant::Ball90, 7), Vec::new());
+        let b = BingoState::new(&config(BingoVariant::Ball90, 7), Vec::new());
+
+        // ログの nonce と order から計算し直せる
+        let joined = format!("{}\n{}", a.nonce, a.order().join("\n"));
+        assert_eq!(a.commitment, blake3::hash(joined.as_bytes()).to_hex().to_string());
+        assert_eq!(a.nonce.len(), 32);
+
+        // 同じ seed でも nonce が違うので commitment は一致しない
+        assert_eq!(a.numbers, b.numbers);
+        assert_ne!(a.commitment, b.commitment);
+    }
 }
@@ -0,0 +1,231 @@
+use crate::{
+    bingo,
+    gamepad::GamepadBindings,
+    keybindings::KeyBindings,
+    loader::{self, EbbPaths, LoadDiagnostics, PresetFile, PresetFormat},
+    server::WsCommand,
+    sound,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use futures_util::{SinkExt, StreamExt};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+#[derive(Parser)]
+#[command(version, about = "Electrical bulletin board for the Kosen Festival")]
+pub struct Cli {
+    /// Directory containing config.toml [default: ~/ebb]
+    #[arg(long, global = true)]
+    pub config_dir: Option<PathBuf>,
+    /// Directory containing preset files [default: <config-dir>/presets]
+    #[arg(long, global = true)]
+    pub preset_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the board (default)
+    Run,
+    /// Check config.toml and every preset file, reporting errors
+    Validate,
+    /// List the presets found in the preset directory
+    ListPresets,
+    /// Send a WebSocket command (JSON) to a running board
+    Send {
+        /// Command JSON, e.g. '{"mode":"bingo","method":"next"}'
+        json: String,
+        #[arg(long, default_value = "ws://127.0.0.1:3000/ws")]
+        url: String,
+    },
+    /// Write a preset out as CSV, TOML, JSON or YAML
+    Export {
+        preset: String,
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+        /// Output file [default: standard output]
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Render a preset to PNG files without opening a window
+    Preview {
+        preset: String,
+        #[arg(long, default_value = "preview")]
+        out_dir: PathBuf,
+        /// Number of images per scrolling text
+        #[arg(long, default_value_t = 8)]
+        frames: u32,
+    },
+    /// List the MIDI input ports that [midi] port can match
+    #[cfg(feature = "midi")]
+    MidiPorts,
+}
+
+// export の --format
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl From<ExportFormat> for PresetFormat {
+    fn from(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Csv => PresetFormat::Csv,
+            ExportFormat::Toml => PresetFormat::Toml,
+            ExportFormat::Json => PresetFormat::Json,
+            ExportFormat::Yaml => PresetFormat::Yaml,
+        }
+    }
+}
+
+pub fn validate(paths: &EbbPaths) -> ExitCode {
+    let mut diagnostics = LoadDiagnostics::default();
+
+    let config_file = paths.config_file();
+    if config_file.exists() {
+        match loader::load_config(paths) {
+            Ok(config) => {
+                println!("ok: {}", config_file.display());
+                // 抽選の名前ファイルや番号の範囲もここで確かめる
+                bingo::new_game(paths, &config.bingo, &mut diagnostics);
+                sound::read_sound_files(paths, &config.sound, &mut diagnostics);
+                KeyBindings::load(paths, &config, &mut diagnostics);
+                GamepadBindings::load(paths, &config, &mut diagnostics);
+                // MIDI なしのビルドでも [midi] の割り当ては確かめる
+                if crate::midi::load(paths, &config, &mut diagnostics).is_some() && !cfg!(feature = "midi") {
+                    diagnostics.push(loader::LoadError::new(&config_file, None, None, "[midi] needs a build with the `midi` feature"));
+                }
+            }
+            Err(e) => diagnostics.push(e),
+        }
+    } else {
+        println!("warning: {} not found, defaults will be used", config_file.display());
+    }
+
+    // プリセットの読み込みエラー・名前の重複・include の問題はここで報告される
+    let preset_manager = loader::load_all_presets(paths, &mut diagnostics);
+    for name in preset_manager.sorted_names() {
+        if preset_manager.presets[&name].is_empty() {
+            println!("warning: preset '{}' has no texts", name);
+        }
+    }
+
+    if diagnostics.errors.is_empty() {
+        println!("ok: no errors");
+        ExitCode::SUCCESS
+    } else {
+        println!("{} error(s) found", diagnostics.errors.len());
+        ExitCode::FAILURE
+    }
+}
+
+pub fn list_presets(paths: &EbbPaths) -> ExitCode {
+    let preset_manager = loader::load_all_presets(paths, &mut LoadDiagnostics::default());
+    for name in preset_manager.sorted_names() {
+        let meta = preset_manager.meta(&name);
+        println!(
+            "{}{}\t{}\t{} texts\t{}",
+            name,
+            if meta.default { " *" } else { "" },
+            meta.title.unwrap_or_default(),
+            preset_manager.presets[&name].len(),
+            meta.tags.join(","),
+        );
+    }
+    ExitCode::SUCCESS
+}
+
+pub fn export(paths: &EbbPaths, name: &str, format: ExportFormat, output: Option<&Path>, force: bool) -> ExitCode {
+    let format = PresetFormat::from(format);
+    // 標準出力には書き出したプリセットだけを出す
+    let mut diagnostics = LoadDiagnostics {
+        quiet: true,
+        ..Default::default()
+    };
+    let preset_manager = loader::load_all_presets(paths, &mut diagnostics);
+    for e in &diagnostics.errors {
+        eprintln!("Err: {}", e);
+    }
+    let Some(texts) = preset_manager.presets.get(name) else {
+        eprintln!("error: preset '{}' not found", name);
+        return ExitCode::FAILURE;
+    };
+    let preset = PresetFile {
+        meta: preset_manager.meta(name),
+        texts: texts.clone(),
+    };
+
+    let exported = match loader::export_preset(&preset, format) {
+        Ok(exported) => exported,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    // 出力先を指定しなければ標準出力へ
+    let Some(path) = output else {
+        print!("{}", exported);
+        return ExitCode::SUCCESS;
+    };
+    // 元のプリセットなどを上書きしないよう、既にあるファイルは --force のときだけ書き換える
+    if path.exists() && !force {
+        eprintln!("error: {} already exists (use --force to overwrite)", path.display());
+        return ExitCode::FAILURE;
+    }
+    if let Err(e) = std::fs::write(path, exported) {
+        eprintln!("error: {}: {}", path.display(), e);
+        return ExitCode::FAILURE;
+    }
+    eprintln!("Exported '{}' to {}", name, path.display());
+    ExitCode::SUCCESS
+}
+
+pub fn send(url: &str, json: &str) -> ExitCode {
+    // 送る前に形式をチェックしておく
+    if let Err(e) = serde_json::from_str::<WsCommand>(json) {
+        println!("error: invalid command: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            println!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    runtime.block_on(async {
+        let (mut socket, _) = match tokio_tungstenite::connect_async(url).await {
+            Ok(connection) => connection,
+            Err(e) => {
+                println!("error: can't connect to {}: {}", url, e);
+                return ExitCode::FAILURE;
+            }
+        };
+        if let Err(e) = socket.send(Message::Text(json.into())).await {
+            println!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+
+        // 応答があれば表示する（応答のないコマンドもあるので待ち時間は短め）
+        if let Ok(Some(Ok(Message::Text(response)))) =
+            tokio::time::timeout(Duration::from_secs(2), socket.next()).await
+        {
+            println!("{}", response);
+        }
+        let _ = socket.close(None).await;
+        ExitCode::SUCCESS
+    })
+}
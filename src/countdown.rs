@@ -1,7 +1,20 @@
+use crate::{
+    action::{broadcast, SetAutoplay, ShowText},
+    loader::{Config, TransitionConfig},
+    server::{CountdownResponse, ResponseBroadcaster, TextResponse, WsResponse},
+    sound::{SoundCue, Sounds},
+    text_spawner,
+    transition::{FadeComponent, FadePhase},
+    Showing,
+};
 use bevy::{
+    color::palettes::tailwind::SLATE_900,
     time::{Timer, TimerMode},
     prelude::*,
 };
+use serde::{Deserialize, Serialize};
+use chrono::{Days, Local};
+use std::time::{Duration, SystemTime};
 
 #[derive(Resource, Default)]
 pub struct CountdownTimer {
@@ -10,11 +23,21 @@ pub struct CountdownTimer {
     pub is_active: bool,
     pub last_displayed_number: i32,
     pub just_finished: bool,
+    pub just_cancelled: bool,
+    pub paused: bool,
+    pub paused_at: Option<SystemTime>, // 一時停止した時刻。時刻指定は止めている間の残り時間をこの時刻で求める
     pub current_number_start_time: f32, // 現在の数字が表示開始された時間
     pub total_elapsed_time: f32, // カウントダウン開始からの総経過時間
     pub mode: CountdownMode, // カウントダウンモード
+    pub format: CountdownFormat,
+    pub label: Option<String>, // 数字の前に付ける文字（"開演まで" など）
+    pub target: Option<SystemTime>, // 時刻指定のときの終了時刻。残り時間はシステム時計から求める
+    pub on_finish: FinishAction,    // 0 になったときの動作
 }
 
+// カウントダウンの残り時間の上限（秒）。時刻指定も翌日のその時刻までなので 1 日あれば足りる
+pub const MAX_SECONDS: f32 = 24.0 * 60.0 * 60.0;
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum CountdownMode {
     #[default]
@@ -23,24 +46,124 @@ pub enum CountdownMode {
     Decelerated, // 線形減速（最初速く、後半遅く）
 }
 
+impl CountdownMode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CountdownMode::Normal => "normal",
+            CountdownMode::Accelerated => "accelerated",
+            CountdownMode::Decelerated => "decelerated",
+        }
+    }
+}
+
+// 残り時間の表示形式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CountdownFormat {
+    #[default]
+    Seconds, // 25
+    MmSs,    // 24:59（60分以上は分が増える）
+    HhMmSs,  // 01:24:59
+}
+
+impl CountdownFormat {
+    pub fn format(&self, seconds: i32) -> String {
+        let seconds = seconds.max(0);
+        match self {
+            CountdownFormat::Seconds => seconds.to_string(),
+            CountdownFormat::MmSs => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+            CountdownFormat::HhMmSs => format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60),
+        }
+    }
+
+    // ストップウォッチ用に 0.1 秒まで表示する
+    pub fn format_tenths(&self, seconds: f32) -> String {
+        let tenths = (seconds.max(0.0) * 10.0) as u64;
+        let (seconds, tenth) = (tenths / 10, tenths % 10);
+        match self {
+            CountdownFormat::Seconds => format!("{}.{}", seconds, tenth),
+            CountdownFormat::MmSs => format!("{:02}:{:02}.{}", seconds / 60, seconds % 60, tenth),
+            CountdownFormat::HhMmSs => format!("{:02}:{:02}:{:02}.{}", seconds / 3600, seconds / 60 % 60, seconds % 60, tenth),
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct CountdownText;
 
+// mm:ss などの長い表示。毎秒作り直さず、同じテキストを書き換える
 #[derive(Component)]
-pub struct FadeComponent {
-    pub fade_in_duration: f32,
-    pub fade_out_duration: f32,
-    pub display_duration: f32,
-    pub current_time: f32,
-    pub phase: FadePhase,
+pub struct SteadyCountdown;
+
+// カウントダウンが 0 になったときの動作
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum FinishAction {
+    #[default]
+    None,                                                 // 0 がフェードアウトして何も表示しない
+    Message { text: String },                             // "START!" などを表示する
+    Preset { preset: String, #[serde(default)] index: usize }, // プリセットのテキストに切り替える
+    Autoplay,                                             // 自動再生を始める
+    Flash,                                                // 画面を点滅させる
+    Hold,                                                 // 0 を表示したままにする
+}
+
+// 画面の点滅（背景色を切り替える）
+#[derive(Resource, Default)]
+pub struct ScreenFlash {
+    timer: Timer,
+    toggles: u32, // 残りの切り替え回数
+}
+
+const FLASH_TOGGLES: u32 = 6;
+const FLASH_INTERVAL: f32 = 0.25;
+
+impl ScreenFlash {
+    pub fn start(&mut self) {
+        self.timer = Timer::from_seconds(FLASH_INTERVAL, TimerMode::Repeating);
+        self.toggles = FLASH_TOGGLES;
+    }
+}
+
+pub fn screen_flash_system(time: Res<Time>, mut flash: ResMut<ScreenFlash>, mut clear_color: ResMut<ClearColor>) {
+    if flash.toggles == 0 {
+        return;
+    }
+    flash.timer.tick(time.delta());
+    if flash.timer.just_finished() {
+        flash.toggles -= 1;
+        // 奇数回目は明るく、最後は元の背景色に戻る
+        clear_color.0 = if flash.toggles % 2 == 1 { Color::WHITE } else { Color::Srgba(SLATE_900) };
+    }
+}
+
+// "15:00" や "15:00:30" を今日のその時刻（ローカル時刻）にする。
+// 過ぎていれば roll_over のときは翌日、そうでなければ None
+pub fn parse_target(text: &str, roll_over: bool) -> Option<SystemTime> {
+    let mut parts = text.trim().split(':').map(|part| part.parse::<u32>().ok());
+    let hour = parts.next()??;
+    let minute = parts.next()??;
+    let second = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let today = local_time(hour, minute, second, 0)?;
+    if today > SystemTime::now() {
+        Some(today)
+    } else if roll_over {
+        local_time(hour, minute, second, 1)
+    } else {
+        None
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum FadePhase {
-    FadeIn,
-    Display,
-    FadeOut,
-    Complete,
+// days_ahead 日後の hour:minute:second（ローカル時刻）。
+// 夏時間の切り替えで存在しない時刻なら None、2回ある時刻なら早いほう
+fn local_time(hour: u32, minute: u32, second: u32, days_ahead: u64) -> Option<SystemTime> {
+    let date = Local::now().date_naive().checked_add_days(Days::new(days_ahead))?;
+    let time = date.and_hms_opt(hour, minute, second)?.and_local_timezone(Local).earliest()?;
+    Some(time.into())
 }
 
 impl CountdownTimer {
@@ -51,15 +174,25 @@ impl CountdownTimer {
             is_active: false,
             last_displayed_number: -1,
             just_finished: false,
+            just_cancelled: false,
+            paused: false,
+            paused_at: None,
             current_number_start_time: 0.0,
             total_elapsed_time: 0.0,
             mode,
+            format: CountdownFormat::Seconds,
+            label: None,
+            target: None,
+            on_finish: FinishAction::None,
         }
     }
     
     pub fn start(&mut self) {
         self.timer.reset();
+        self.target = None;
         self.is_active = true;
+        self.paused = false;
+        self.paused_at = None;
         self.last_displayed_number = -1;
         self.just_finished = false;
         self.current_number_start_time = 0.0;
@@ -68,14 +201,107 @@ impl CountdownTimer {
     
     pub fn stop(&mut self) {
         self.is_active = false;
+        self.paused = false;
+        self.paused_at = None;
         self.last_displayed_number = -1;
         self.just_finished = false;
         self.current_number_start_time = 0.0;
         self.total_elapsed_time = 0.0;
     }
+
+    // 指定した時刻までのカウントダウンを始める（通常モード）
+    pub fn start_until(&mut self, target: SystemTime) {
+        let remaining = target.duration_since(SystemTime::now()).unwrap_or_default();
+        self.mode = CountdownMode::Normal;
+        self.initial_seconds = remaining.as_secs_f32();
+        self.timer = Timer::new(remaining, TimerMode::Once);
+        self.start();
+        self.target = Some(target);
+    }
+
+    pub fn pause(&mut self) -> bool {
+        if !self.is_active || self.paused {
+            return false;
+        }
+        self.paused = true;
+        self.paused_at = Some(SystemTime::now());
+        true
+    }
+
+    pub fn resume(&mut self) -> bool {
+        if !self.is_active || !self.paused {
+            return false;
+        }
+        self.paused = false;
+        // 時刻指定は止めていた分だけ終了時刻を遅らせる
+        if let (Some(target), Some(paused_at)) = (self.target, self.paused_at.take()) {
+            self.target = Some(target + SystemTime::now().duration_since(paused_at).unwrap_or_default());
+        }
+        // 止めている間に薄れた数字を出し直す
+        self.last_displayed_number = -1;
+        true
+    }
+
+    // 途中でやめる。countdown_finished_system が "cancelled" を知らせる
+    pub fn cancel(&mut self) -> bool {
+        if !self.is_active {
+            return false;
+        }
+        self.stop();
+        self.just_cancelled = true;
+        true
+    }
+
+    // 加速・減速モードは 10→0 を決まった間隔で数えるので、残り時間を秒単位では動かせない
+    pub fn is_adjustable(&self) -> bool {
+        self.mode == CountdownMode::Normal
+    }
+
+    // 残り時間を増減する（マイナスで短くする）。実際に増減した秒数を返す。
+    // 残り時間は 0 から MAX_SECONDS までに収める
+    pub fn add_seconds(&mut self, seconds: f32) -> Option<f32> {
+        if !self.is_active || !seconds.is_finite() || !self.is_adjustable() {
+            return None;
+        }
+        if self.target.is_some() {
+            // 時刻指定は終了時刻そのものをずらす（一時停止中は止めた時刻から数える）
+            let remaining = self.remaining_seconds();
+            let adjusted = (remaining + seconds).clamp(0.0, MAX_SECONDS);
+            self.target = Some(self.paused_at.unwrap_or_else(SystemTime::now) + Duration::from_secs_f32(adjusted));
+            Some(adjusted - remaining)
+        } else {
+            let elapsed = self.timer.elapsed_secs();
+            let remaining = self.timer.remaining_secs();
+            let adjusted = (remaining + seconds).clamp(0.0, MAX_SECONDS);
+            self.timer.set_duration(Duration::from_secs_f32(elapsed + adjusted));
+            Some(adjusted - remaining)
+        }
+    }
+
+    // WebSocket から受け取った秒数で始められるか（Timer は負の値や無限大を受け付けない）
+    pub fn is_valid_seconds(seconds: f32) -> bool {
+        seconds.is_finite() && seconds > 0.0 && seconds <= MAX_SECONDS
+    }
     
+    pub fn display_text(&self, seconds: i32) -> String {
+        let time = self.format.format(seconds);
+        match &self.label {
+            Some(label) => format!("{} {}", label, time),
+            None => time,
+        }
+    }
+
+    // 秒だけの短いカウントダウン以外はフェードせずに表示する
+    pub fn is_steady(&self) -> bool {
+        self.format != CountdownFormat::Seconds || self.label.is_some()
+    }
+
     pub fn remaining_seconds(&self) -> f32 {
-        if self.is_active {
+        if let (true, Some(target)) = (self.is_active, self.target) {
+            // フレームが止まっても、再起動しても正しい残り時間になるよう時計から求める（一時停止中は止めた時刻で）
+            let now = self.paused_at.unwrap_or_else(SystemTime::now);
+            target.duration_since(now).unwrap_or_default().as_secs_f32()
+        } else if self.is_active {
             self.timer.remaining_secs()
         } else {
             self.initial_seconds
@@ -147,21 +373,35 @@ impl CountdownTimer {
     }
 }
 
-pub fn setup_countdown_timer(mut commands: Commands) {
-    commands.insert_resource(CountdownTimer::new(10.0, CountdownMode::Normal));
-}
+// mm:ss などの長い表示でティック音を鳴らす残り秒数
+const TICK_SECONDS: i32 = 10;
 
+#[allow(clippy::too_many_arguments)]
 pub fn countdown_system(
     time: Res<Time>,
     mut countdown_timer: ResMut<CountdownTimer>,
     mut commands: Commands,
     fonts: Res<crate::Fonts>,
-    text_query: Query<Entity, With<CountdownText>>,
+    text_query: Query<(Entity, Has<SteadyCountdown>, Has<Showing>), With<CountdownText>>,
+    mut fade_query: Query<&mut FadeComponent, With<CountdownText>>,
+    sounds: Res<Sounds>,
+    mut writer: Text2dWriter,
 ) {
     if !countdown_timer.is_active {
         return;
     }
 
+    // 一時停止中は今の数字を消さずに表示し続ける
+    if countdown_timer.paused {
+        for mut fade in fade_query.iter_mut() {
+            if fade.phase != FadePhase::FadeIn {
+                fade.phase = FadePhase::Display;
+                fade.current_time = 0.0;
+            }
+        }
+        return;
+    }
+
     countdown_timer.timer.tick(time.delta());
     countdown_timer.total_elapsed_time += time.delta_secs();
     
@@ -178,24 +418,46 @@ pub fn countdown_system(
     
     // 数字が変わった場合のみ更新
     if current_number != countdown_timer.last_displayed_number {
-        // 既存のカウントダウンテキストを削除
-        for entity in text_query.iter() {
-            commands.entity(entity).despawn();
+        let display_text = countdown_timer.display_text(current_number);
+
+        // 長い表示は毎秒フェードさせず、表示中のものがあればそのまま書き換える
+        let steady = countdown_timer.is_steady()
+            .then(|| text_query.iter().find(|(_, steady, showing)| *steady && *showing))
+            .flatten()
+            .map(|(entity, _, _)| entity);
+
+        // それ以外の既存のカウントダウンテキストを削除
+        for (entity, _, _) in text_query.iter() {
+            if Some(entity) != steady {
+                commands.entity(entity).despawn();
+            }
+        }
+
+        if let Some(entity) = steady {
+            text_spawner::replace_segments(&mut commands, &mut writer, entity, fonts.plain(&display_text));
+        } else if countdown_timer.is_steady() {
+            text_spawner::spawn_static_text(&mut commands, fonts.plain(&display_text), &TransitionConfig::default())
+                .insert((CountdownText, SteadyCountdown));
+        } else {
+            // 新しいカウントダウンテキストを表示（フェードイン/アウト付き）
+            spawn_countdown_text(&mut commands, &display_text, fonts.text_font.clone());
         }
         
-        let display_text = current_number.to_string();
-        
-        // 新しいカウントダウンテキストを表示（フェードイン/アウト付き）
-        spawn_countdown_text(&mut commands, &display_text, fonts.text_font.clone());
-        
+        // 長い表示では最後の 10 秒だけ鳴らす
+        if current_number > 0 && (!countdown_timer.is_steady() || current_number <= TICK_SECONDS) {
+            sounds.play(&mut commands, SoundCue::Tick);
+        }
+
         countdown_timer.last_displayed_number = current_number;
         countdown_timer.current_number_start_time = countdown_timer.total_elapsed_time;
         
-        println!("Countdown: {} (elapsed: {:.2}s)", current_number, countdown_timer.total_elapsed_time);
+        println!("Countdown: {} (elapsed: {:.2}s)", display_text, countdown_timer.total_elapsed_time);
     }
     
-    // タイマー終了チェック（変動モードでは固定時間×10、通常モードは設定時間）
-    let should_finish = if countdown_timer.mode != CountdownMode::Normal {
+    // タイマー終了チェック（変動モードでは固定時間×10、通常モードは設定時間、時刻指定はその時刻）
+    let should_finish = if countdown_timer.target.is_some() {
+        countdown_timer.remaining_seconds() <= 0.0
+    } else if countdown_timer.mode != CountdownMode::Normal {
         countdown_timer.total_elapsed_time >= 15.5 // 1.3～1.8秒×10 ≈ 15.5秒
     } else {
         countdown_timer.timer.is_finished()
@@ -204,69 +466,7 @@ pub fn countdown_system(
     if should_finish && countdown_timer.is_active {
         countdown_timer.stop();
         countdown_timer.just_finished = true;
-        println!("Countdown finished! Ready for exit guidance.");
-    }
-}
-
-pub fn fade_system(
-    time: Res<Time>,
-    mut query: Query<(Entity, &mut FadeComponent, &mut TextColor), With<CountdownText>>,
-    mut commands: Commands,
-) {
-    let mut entities_to_remove = Vec::new();
-    
-    for (entity, mut fade, mut text_color) in query.iter_mut() {
-        fade.current_time += time.delta_secs();
-        
-        let alpha = match fade.phase {
-            FadePhase::FadeIn => {
-                if fade.current_time >= fade.fade_in_duration {
-                    fade.phase = FadePhase::Display;
-                    fade.current_time = 0.0;
-                    1.0
-                } else {
-                    fade.current_time / fade.fade_in_duration
-                }
-            }
-            FadePhase::Display => {
-                if fade.current_time >= fade.display_duration {
-                    fade.phase = FadePhase::FadeOut;
-                    fade.current_time = 0.0;
-                }
-                1.0
-            }
-            FadePhase::FadeOut => {
-                if fade.current_time >= fade.fade_out_duration {
-                    fade.phase = FadePhase::Complete;
-                    0.0
-                } else {
-                    1.0 - (fade.current_time / fade.fade_out_duration)
-                }
-            }
-            FadePhase::Complete => 0.0,
-        };
-        
-        // アルファ値を適用
-        let mut color = text_color.0;
-        color.set_alpha(alpha.clamp(0.0, 1.0));
-        text_color.0 = color;
-        
-        // フェードアウト完了時にエンティティを削除リストに追加
-        if fade.phase == FadePhase::Complete {
-            entities_to_remove.push(entity);
-        }
-    }
-    
-    // 遅延削除でエンティティを安全に削除
-    for entity in entities_to_remove {
-        match commands.get_entity(entity) {
-            Ok(mut entity_commands) => {
-                entity_commands.despawn();
-            }
-            Err(_) => {
-                // エンティティが既に削除されている場合は無視
-            }
-        }
+        println!("Countdown finished! ({:?})", countdown_timer.on_finish);
     }
 }
 
@@ -282,30 +482,169 @@ fn spawn_countdown_text(
         Transform::from_xyz(0.0, 0.0, 0.0),
         TextLayout::default(),
         CountdownText,
-        FadeComponent {
-            fade_in_duration: 0.3,
-            fade_out_duration: 0.3,
-            display_duration: 0.4,
-            current_time: 0.0,
-            phase: FadePhase::FadeIn,
-        },
+        FadeComponent::new(0.3, 0.4, 0.3),
         crate::Showing,
     ));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn countdown_finished_system(
+    mut commands: Commands,
     mut countdown_timer: ResMut<CountdownTimer>,
-    ws_channel: Option<ResMut<crate::server::WebSocketChannel>>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+    text_query: Query<(Entity, Has<SteadyCountdown>), With<CountdownText>>,
+    showing_query: Query<Entity, With<crate::Showing>>,
+    mut fade_query: Query<&mut FadeComponent, With<CountdownText>>,
+    fonts: Res<crate::Fonts>,
+    config: Res<Config>,
+    mut flash: ResMut<ScreenFlash>,
+    sounds: Res<Sounds>,
+    text_queue: Res<crate::TextQueue>,
+    mut show_text: MessageWriter<ShowText>,
+    mut set_autoplay: MessageWriter<SetAutoplay>,
 ) {
+    if countdown_timer.just_cancelled {
+        countdown_timer.just_cancelled = false;
+
+        // 表示中の数字をすぐに消す
+        for (entity, _) in text_query.iter() {
+            commands.entity(entity).despawn();
+        }
+        println!("Countdown cancelled");
+
+        broadcast(&broadcaster, WsResponse::Countdown(CountdownResponse {
+            status: "cancelled".to_string(),
+        }));
+    }
+
     if countdown_timer.just_finished {
         countdown_timer.just_finished = false;
-        
+
+        sounds.play(&mut commands, SoundCue::Buzzer);
+
+        // 0 になったときの動作
+        match countdown_timer.on_finish.clone() {
+            FinishAction::None => {}
+            FinishAction::Message { text } => {
+                crate::transition::leave_all(&mut commands, showing_query.iter(), &config.transition);
+                crate::text_spawner::spawn_static_text(&mut commands, fonts.plain(&text), &config.transition);
+                broadcast(&broadcaster, WsResponse::Text(TextResponse {
+                    status: "shown".to_string(),
+                    text,
+                    next_text: text_queue.next_content(),
+                }));
+            }
+            FinishAction::Preset { preset, index } => {
+                show_text.write(ShowText {
+                    preset: Some(preset),
+                    index: Some(index),
+                    transition: None,
+                });
+            }
+            // 0 が消えたら自動再生が次のテキストを出す
+            FinishAction::Autoplay => {
+                set_autoplay.write(SetAutoplay { enabled: Some(true) });
+            }
+            FinishAction::Flash => flash.start(),
+            // 秒だけの表示の 0 を消さずに残す（長い表示は下でフェードさせないので残る）
+            FinishAction::Hold => {
+                for mut fade in fade_query.iter_mut() {
+                    fade.phase = FadePhase::Display;
+                    fade.display_duration = f32::INFINITY;
+                }
+            }
+        }
+
+        // mm:ss などの長い表示はフェードしないので、秒だけの表示と同じく少し見せてから消す。
+        // hold はそのまま残し、message は上で片付け済み
+        if !matches!(countdown_timer.on_finish, FinishAction::Hold | FinishAction::Message { .. }) {
+            for (entity, steady) in text_query.iter() {
+                if steady {
+                    commands.entity(entity).insert(FadeComponent::new(0.0, 0.7, 0.3));
+                }
+            }
+        }
+
         // WebSocketでカウントダウン終了を通知
-        if let Some(ws_channel) = ws_channel {
-            let response = crate::server::WsResponse::Countdown(crate::server::CountdownResponse {
-                status: "finished".to_string(),
-            });
-            let _ = ws_channel.response_sender.send(response);
+        broadcast(&broadcaster, WsResponse::Countdown(CountdownResponse {
+            status: "finished".to_string(),
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_target_keeps_remaining_time() {
+        let mut timer = CountdownTimer::new(10.0, CountdownMode::Normal);
+        timer.start_until(SystemTime::now() + Duration::from_secs(100));
+        assert!(timer.pause());
+
+        // 30 秒前に残り 100 秒で止めたことにする
+        let ago = Duration::from_secs(30);
+        timer.target = timer.target.map(|target| target - ago);
+        timer.paused_at = timer.paused_at.map(|paused_at| paused_at - ago);
+        assert!((timer.remaining_seconds() - 100.0).abs() < 1.0);
+
+        assert_eq!(timer.add_seconds(-10.0).map(f32::round), Some(-10.0));
+        assert!((timer.remaining_seconds() - 90.0).abs() < 1.0);
+
+        assert!(timer.resume());
+        assert!((timer.remaining_seconds() - 90.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn add_seconds_reports_clamped_amount() {
+        let mut timer = CountdownTimer::new(10.0, CountdownMode::Normal);
+        assert_eq!(timer.add_seconds(5.0), None);
+
+        timer.start();
+        assert_eq!(timer.add_seconds(5.0), Some(5.0));
+        assert_eq!(timer.add_seconds(-100.0), Some(-15.0));
+        assert_eq!(timer.add_seconds(f32::INFINITY), None);
+        assert_eq!(timer.add_seconds(1e9), Some(MAX_SECONDS));
+
+        // 加速・減速モードは調整できない
+        let mut accelerated = CountdownTimer::new(15.0, CountdownMode::Accelerated);
+        accelerated.start();
+        assert!(!accelerated.is_adjustable());
+        assert_eq!(accelerated.add_seconds(10.0), None);
+    }
+
+    #[test]
+    fn formats_whole_seconds() {
+        assert_eq!(CountdownFormat::Seconds.format(25), "25");
+        assert_eq!(CountdownFormat::Seconds.format(-3), "0");
+        assert_eq!(CountdownFormat::MmSs.format(1499), "24:59");
+        assert_eq!(CountdownFormat::MmSs.format(3723), "62:03");
+        assert_eq!(CountdownFormat::HhMmSs.format(5099), "01:24:59");
+    }
+
+    #[test]
+    fn formats_tenths() {
+        assert_eq!(CountdownFormat::Seconds.format_tenths(3.25), "3.2");
+        assert_eq!(CountdownFormat::Seconds.format_tenths(-1.0), "0.0");
+        assert_eq!(CountdownFormat::MmSs.format_tenths(61.5), "01:01.5");
+        assert_eq!(CountdownFormat::HhMmSs.format_tenths(3661.9), "01:01:01.9");
+    }
+
+    #[test]
+    fn rejects_malformed_targets() {
+        for text in ["", "12", "24:00", "12:60", "12:00:60", "a:b", "1:2:3:4", "-1:00"] {
+            assert_eq!(parse_target(text, true), None, "{}", text);
         }
     }
+
+    #[test]
+    fn past_target_rolls_over_to_tomorrow() {
+        // 今日の 0:00 は必ず過ぎている
+        assert_eq!(parse_target("0:00", false), None);
+
+        let target = parse_target(" 00:00:00 ", true).unwrap();
+        let remaining = target.duration_since(SystemTime::now()).unwrap();
+        // 夏時間の切り替えがあっても 25 時間以内
+        assert!(remaining <= Duration::from_secs(25 * 60 * 60));
+    }
 }
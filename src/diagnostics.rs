@@ -0,0 +1,105 @@
+use crate::{
+    loader::{LoadDiagnostics, LoadError},
+    Fonts,
+};
+use bevy::{color::palettes::tailwind::RED_400, prelude::*};
+use std::{collections::HashSet, path::Path};
+
+// 起動時の読み込みエラーを表示しておく時間（秒）
+const OVERLAY_SECONDS: f32 = 30.0;
+
+#[derive(Component)]
+pub struct DiagnosticOverlay {
+    timer: Timer,
+}
+
+// 読み込めなかったフォントを読み込みエラーとして記録する
+pub fn check_font_loads(
+    asset_server: Res<AssetServer>,
+    fonts: Res<Fonts>,
+    mut diagnostics: ResMut<LoadDiagnostics>,
+    mut reported: Local<HashSet<AssetId<Font>>>,
+) {
+    for handle in fonts.handles() {
+        if reported.contains(&handle.id()) || !asset_server.load_state(&handle).is_failed() {
+            continue;
+        }
+        reported.insert(handle.id());
+        let path = handle.path().map(|path| path.path().to_path_buf()).unwrap_or_default();
+        diagnostics.push(LoadError::new(
+            &Path::new("assets").join(path),
+            None,
+            None,
+            "font file could not be loaded",
+        ));
+    }
+}
+
+// 読み込みエラーがあれば画面左上に一覧を表示する（エラーが増えたら表示し直す）
+pub fn show_load_diagnostics(
+    mut cmds: Commands,
+    asset_server: Res<AssetServer>,
+    diagnostics: Res<LoadDiagnostics>,
+    fonts: Res<Fonts>,
+    overlays: Query<Entity, With<DiagnosticOverlay>>,
+) {
+    if !diagnostics.is_changed() || diagnostics.errors.is_empty() {
+        return;
+    }
+    for entity in overlays.iter() {
+        cmds.entity(entity).despawn();
+    }
+
+    // 標準フォントが読めない場合は Bevy 組み込みのフォントで表示する
+    let font = if asset_server.load_state(&fonts.text_font.font).is_failed() {
+        Handle::default()
+    } else {
+        fonts.text_font.font.clone()
+    };
+
+    let mut message = String::from("Load errors:");
+    for e in &diagnostics.errors {
+        message.push('\n');
+        message.push_str(&e.to_string());
+    }
+
+    cmds.spawn((
+        Text::new(message),
+        TextFont {
+            font,
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::Srgba(RED_400)),
+        BackgroundColor(Color::BLACK.with_alpha(0.8)),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.0),
+            top: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        DiagnosticOverlay {
+            timer: Timer::from_seconds(OVERLAY_SECONDS, TimerMode::Once),
+        },
+    ));
+}
+
+// 一定時間たつか何かキーが押されたら消す。消すのに使ったキーは操作として扱わない
+pub fn hide_load_diagnostics(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut query: Query<(Entity, &mut DiagnosticOverlay)>,
+) {
+    let pressed = keys.get_just_pressed().next().is_some();
+    if pressed && !query.is_empty() {
+        keys.clear();
+    }
+    for (entity, mut overlay) in &mut query {
+        overlay.timer.tick(time.delta());
+        if overlay.timer.is_finished() || pressed {
+            cmds.entity(entity).despawn();
+        }
+    }
+}
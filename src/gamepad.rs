@@ -0,0 +1,76 @@
+use crate::{
+    action::{InputAction, InputActions},
+    keybindings::{self, Bindings},
+    loader::{Config, EbbPaths, LoadDiagnostics},
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::fmt;
+
+// ゲームパッドのボタン。名前は Bevy の GamepadButton（"South"、"Start"、"DPadUp" など）
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct PadButton(pub GamepadButton);
+
+impl TryFrom<String> for PadButton {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        keybindings::unit_variant(&name).map(PadButton).ok_or_else(|| format!("unknown gamepad button `{}`", name))
+    }
+}
+
+impl fmt::Display for PadButton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+pub type GamepadBindings = Bindings<PadButton>;
+
+// ステージでよく使う操作だけ既定で割り当てておく
+fn default_buttons(action: InputAction) -> Vec<PadButton> {
+    let button = match action {
+        InputAction::NextText => GamepadButton::South,
+        InputAction::BingoNext => GamepadButton::East,
+        InputAction::CountdownNormal => GamepadButton::West,
+        InputAction::StopwatchToggle => GamepadButton::North,
+        InputAction::ToggleAutoplay => GamepadButton::Start,
+        InputAction::ToggleHelp => GamepadButton::Select,
+        InputAction::CountdownPause => GamepadButton::RightTrigger,
+        InputAction::CountdownCancel => GamepadButton::LeftTrigger,
+        InputAction::CountdownAddTime => GamepadButton::DPadUp,
+        InputAction::CountdownRemoveTime => GamepadButton::DPadDown,
+        _ => return Vec::new(),
+    };
+    vec![PadButton(button)]
+}
+
+impl GamepadBindings {
+    pub fn load(paths: &EbbPaths, config: &Config, diagnostics: &mut LoadDiagnostics) -> Self {
+        Self::parse("gamepad", &config.gamepad, default_buttons, paths, diagnostics)
+    }
+}
+
+// どのゲームパッドのボタンでも操作できる
+pub fn gamepad_input_system(
+    gamepads: Query<&Gamepad>,
+    bindings: Res<GamepadBindings>,
+    mut actions: InputActions,
+) {
+    for gamepad in gamepads.iter() {
+        for action in bindings.triggered(|button| gamepad.just_pressed(button.0)) {
+            actions.send(action);
+        }
+    }
+}
+
+// つながったゲームパッドを表示する（仮想デバイスで試すときの確認用）
+pub fn log_gamepads(gamepads: Query<(Entity, Option<&Name>), Added<Gamepad>>) {
+    for (entity, name) in gamepads.iter() {
+        match name {
+            Some(name) => println!("Gamepad connected: {} ({})", name, entity),
+            None => println!("Gamepad connected: {}", entity),
+        }
+    }
+}
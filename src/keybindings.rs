@@ -0,0 +1,395 @@
+use crate::{
+    action::InputAction,
+    gamepad::{GamepadBindings, PadButton},
+    loader::{Config, EbbPaths, LoadDiagnostics, LoadError},
+    midi::MidiBindings,
+    Fonts,
+};
+use bevy::{
+    color::palettes::tailwind::SLATE_100,
+    prelude::*,
+    reflect::{DynamicEnum, DynamicVariant, TypeInfo, Typed, VariantInfo},
+};
+use serde::{
+    de::{self, DeserializeOwned, IntoDeserializer, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{collections::HashMap, fmt, hash::Hash, marker::PhantomData};
+
+// "Ctrl+Shift+B" のようなキーの組み合わせ。修飾キーは押されている組み合わせが完全に一致したときだけ反応する。
+// "ShiftLeft" のように修飾キーそのものを割り当てたときは、そのキーの種類（Shift）の押し具合は問わない
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct KeyCombo {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub super_key: bool,
+}
+
+impl KeyCombo {
+    const fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+            super_key: false,
+        }
+    }
+
+    pub fn just_pressed(&self, keys: &ButtonInput<KeyCode>) -> bool {
+        let modifier = |wanted: bool, group: [KeyCode; 2]| group.contains(&self.key) || wanted == keys.any_pressed(group);
+        keys.just_pressed(self.key)
+            && modifier(self.ctrl, [KeyCode::ControlLeft, KeyCode::ControlRight])
+            && modifier(self.shift, [KeyCode::ShiftLeft, KeyCode::ShiftRight])
+            && modifier(self.alt, [KeyCode::AltLeft, KeyCode::AltRight])
+            && modifier(self.super_key, [KeyCode::SuperLeft, KeyCode::SuperRight])
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|key| !key.is_empty()).ok_or_else(|| format!("missing key in `{}`", text))?;
+        let mut combo = KeyCombo::new(parse_key(key).ok_or_else(|| format!("unknown key `{}`", key))?);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => combo.ctrl = true,
+                "shift" => combo.shift = true,
+                "alt" | "option" => combo.alt = true,
+                "super" | "meta" | "cmd" | "win" => combo.super_key = true,
+                _ => return Err(format!("unknown modifier `{}`", modifier)),
+            }
+        }
+        Ok(combo)
+    }
+}
+
+// 1文字の英数字はそのまま書ける（"A" → KeyA、"1" → Digit1）。それ以外は Bevy の KeyCode の名前（"Enter"、"Numpad0"、"F13" など）
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return key_code(&format!("Key{}", c.to_ascii_uppercase()));
+        }
+        if c.is_ascii_digit() {
+            return key_code(&format!("Digit{}", c));
+        }
+    }
+    key_code(name)
+}
+
+fn key_code(name: &str) -> Option<KeyCode> {
+    unit_variant(name)
+}
+
+// Bevy の入力の enum（KeyCode・GamepadButton）を名前から作る
+pub fn unit_variant<T: Typed + FromReflect>(name: &str) -> Option<T> {
+    let TypeInfo::Enum(info) = T::type_info() else {
+        return None;
+    };
+    let VariantInfo::Unit(_) = info.variant(name)? else {
+        return None;
+    };
+    T::from_reflect(&DynamicEnum::new(name, DynamicVariant::Unit))
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl"), (self.shift, "Shift"), (self.alt, "Alt"), (self.super_key, "Super")] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        let name = format!("{:?}", self.key);
+        let short = name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")).filter(|rest| rest.len() == 1);
+        write!(f, "{}", short.unwrap_or(&name))
+    }
+}
+
+// [keybindings] などの値。1つならそのまま、複数なら配列で書ける（空の配列でその操作を無効にする）
+#[derive(Debug, Clone)]
+pub struct BindingList<T>(Vec<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for BindingList<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BindingListVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for BindingListVisitor<T> {
+            type Value = BindingList<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a binding such as \"Ctrl+B\" or an array of bindings")
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Self::Value, E> {
+                T::deserialize(text.into_deserializer()).map(|one| BindingList(vec![one]))
+            }
+
+            fn visit_i64<E: de::Error>(self, number: i64) -> Result<Self::Value, E> {
+                T::deserialize(number.into_deserializer()).map(|one| BindingList(vec![one]))
+            }
+
+            fn visit_u64<E: de::Error>(self, number: u64) -> Result<Self::Value, E> {
+                T::deserialize(number.into_deserializer()).map(|one| BindingList(vec![one]))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut all = Vec::new();
+                while let Some(one) = seq.next_element()? {
+                    all.push(one);
+                }
+                Ok(BindingList(all))
+            }
+        }
+
+        deserializer.deserialize_any(BindingListVisitor(PhantomData))
+    }
+}
+
+// 操作ごとの割り当て（キー・ゲームパッドのボタン・MIDI のノートなど）。設定で指定しなかった操作は既定のまま
+#[derive(Resource, Debug, Clone)]
+pub struct Bindings<T: Send + Sync + 'static> {
+    pub bindings: Vec<(InputAction, Vec<T>)>,
+}
+
+impl<T: Copy + Eq + Hash + fmt::Display + Send + Sync + 'static> Bindings<T> {
+    // config.toml の [table] を1項目ずつ読む。知らない操作や読めない値はその項目だけ読み込みエラーにして既定のままにする
+    pub fn parse(
+        table: &str,
+        raw: &toml::Table,
+        default: impl Fn(InputAction) -> Vec<T>,
+        paths: &EbbPaths,
+        diagnostics: &mut LoadDiagnostics,
+    ) -> Self
+    where
+        T: DeserializeOwned,
+    {
+        let mut config: HashMap<InputAction, Vec<T>> = HashMap::new();
+        for (name, value) in raw {
+            let Some(action) = InputAction::ALL.into_iter().find(|action| action.code() == name) else {
+                diagnostics.push(LoadError::new(&paths.config_file(), None, None, format!("[{}] unknown action `{}`", table, name)));
+                continue;
+            };
+            match value.clone().try_into::<BindingList<T>>() {
+                Ok(list) => {
+                    config.insert(action, list.0);
+                }
+                Err(e) => diagnostics.push(LoadError::new(
+                    &paths.config_file(),
+                    None,
+                    None,
+                    format!("[{}] {}: {}", table, name, e.message()),
+                )),
+            }
+        }
+
+        let bindings = Self {
+            bindings: InputAction::ALL.into_iter()
+                .map(|action| (action, config.remove(&action).unwrap_or_else(|| default(action))))
+                .collect(),
+        };
+        bindings.report_conflicts(table, paths, diagnostics);
+        bindings
+    }
+
+    // 同じキーなどが複数の操作に割り当てられていれば読み込みエラーとして報告する
+    fn report_conflicts(&self, table: &str, paths: &EbbPaths, diagnostics: &mut LoadDiagnostics) {
+        let mut seen: HashMap<T, InputAction> = HashMap::new();
+        for (action, all) in &self.bindings {
+            for one in all {
+                if let Some(other) = seen.insert(*one, *action) {
+                    diagnostics.push(LoadError::new(
+                        &paths.config_file(),
+                        None,
+                        None,
+                        format!("[{}] `{}` is bound to both {} and {}", table, one, other.code(), action.code()),
+                    ));
+                }
+            }
+        }
+    }
+
+    // pressed が true になった割り当てのある操作
+    pub fn triggered<'a>(&'a self, pressed: impl Fn(&T) -> bool + 'a) -> impl Iterator<Item = InputAction> + 'a {
+        self.bindings.iter()
+            .filter(move |(_, all)| all.iter().any(&pressed))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn get(&self, action: InputAction) -> &[T] {
+        self.bindings.iter()
+            .find(|(other, _)| *other == action)
+            .map(|(_, all)| all.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+pub type KeyBindings = Bindings<KeyCombo>;
+
+impl KeyBindings {
+    pub fn load(paths: &EbbPaths, config: &Config, diagnostics: &mut LoadDiagnostics) -> Self {
+        Self::parse("keybindings", &config.keybindings, |action| vec![KeyCombo::new(default_key(action))], paths, diagnostics)
+    }
+}
+
+fn default_key(action: InputAction) -> KeyCode {
+    match action {
+        InputAction::NextText => KeyCode::Enter,
+        InputAction::ToggleAutoplay => KeyCode::KeyA,
+        InputAction::ToggleMute => KeyCode::KeyM,
+        InputAction::BingoNext => KeyCode::KeyB,
+        InputAction::CountdownNormal => KeyCode::KeyC,
+        InputAction::CountdownAccelerated => KeyCode::KeyX,
+        InputAction::CountdownDecelerated => KeyCode::KeyZ,
+        InputAction::CountdownPause => KeyCode::KeyP,
+        InputAction::CountdownCancel => KeyCode::Backspace,
+        InputAction::CountdownAddTime => KeyCode::ArrowUp,
+        InputAction::CountdownRemoveTime => KeyCode::ArrowDown,
+        InputAction::StopwatchToggle => KeyCode::KeyS,
+        InputAction::StopwatchLap => KeyCode::KeyL,
+        InputAction::StopwatchReset => KeyCode::KeyR,
+        InputAction::ToggleHelp => KeyCode::F1,
+    }
+}
+
+// 今のキー割り当ての一覧（ヘルプ）
+#[derive(Resource, Default)]
+pub struct HelpOverlay {
+    pub visible: bool,
+}
+
+#[derive(Component)]
+pub struct HelpText;
+
+pub fn help_overlay_system(
+    mut cmds: Commands,
+    help: Res<HelpOverlay>,
+    bindings: Res<KeyBindings>,
+    pad_bindings: Res<GamepadBindings>,
+    midi_bindings: Option<Res<MidiBindings>>,
+    fonts: Res<Fonts>,
+    query: Query<Entity, With<HelpText>>,
+) {
+    if !help.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        cmds.entity(entity).despawn();
+    }
+    if !help.visible {
+        return;
+    }
+
+    let mut message = String::from("Key bindings:");
+    for (action, combos) in &bindings.bindings {
+        let keys: Vec<String> = combos.iter().map(KeyCombo::to_string).collect();
+        let keys = if keys.is_empty() { "-".to_string() } else { keys.join(", ") };
+        message.push_str(&format!("\n{:<20} {}", keys, action.description()));
+        // ゲームパッドのボタンも併せて出す
+        let buttons: Vec<String> = pad_bindings.get(*action).iter().map(PadButton::to_string).collect();
+        if !buttons.is_empty() {
+            message.push_str(&format!(" (pad: {})", buttons.join(", ")));
+        }
+        // MIDI コントローラーをつないでいればその割り当ても
+        let midi = midi_bindings.as_ref().map(|midi| midi.describe(*action)).unwrap_or_default();
+        if !midi.is_empty() {
+            message.push_str(&format!(" (midi: {})", midi.join(", ")));
+        }
+    }
+
+    cmds.spawn((
+        Text::new(message),
+        TextFont {
+            font: fonts.text_font.font.clone(),
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::Srgba(SLATE_100)),
+        BackgroundColor(Color::BLACK.with_alpha(0.8)),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(8.0),
+            top: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        HelpText,
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combo(text: &str) -> Result<KeyCombo, String> {
+        KeyCombo::try_from(text.to_string())
+    }
+
+    fn parse(toml: &str) -> (KeyBindings, Vec<String>) {
+        let paths = EbbPaths::new(Some(std::env::temp_dir()), None);
+        let mut diagnostics = LoadDiagnostics { quiet: true, ..default() };
+        let table: toml::Table = toml::from_str(toml).unwrap();
+        let bindings = KeyBindings::parse("keybindings", &table, |action| vec![KeyCombo::new(default_key(action))], &paths, &mut diagnostics);
+        (bindings, diagnostics.errors.into_iter().map(|e| e.reason).collect())
+    }
+
+    #[test]
+    fn parses_key_combos() {
+        assert_eq!(combo("Ctrl+Shift+B"), Ok(KeyCombo { ctrl: true, shift: true, ..KeyCombo::new(KeyCode::KeyB) }));
+        assert_eq!(combo("cmd + option + 1"), Ok(KeyCombo { super_key: true, alt: true, ..KeyCombo::new(KeyCode::Digit1) }));
+        assert_eq!(combo("a"), Ok(KeyCombo::new(KeyCode::KeyA)));
+        assert_eq!(combo("F13"), Ok(KeyCombo::new(KeyCode::F13)));
+        assert_eq!(combo("ShiftLeft"), Ok(KeyCombo::new(KeyCode::ShiftLeft)));
+
+        assert_eq!(combo("Hyper+A"), Err("unknown modifier `Hyper`".to_string()));
+        assert_eq!(combo("Ctrl+"), Err("missing key in `Ctrl+`".to_string()));
+        assert_eq!(combo("Foo"), Err("unknown key `Foo`".to_string()));
+    }
+
+    #[test]
+    fn displays_key_combos() {
+        for text in ["Ctrl+B", "Ctrl+Shift+Alt+Super+1", "Enter", "F13", "ArrowUp"] {
+            assert_eq!(combo(text).unwrap().to_string(), text);
+        }
+        assert_eq!(combo("shift+digit1").map(|combo| combo.to_string()), Err("unknown key `digit1`".to_string()));
+    }
+
+    #[test]
+    fn binding_lists_accept_one_or_many() {
+        let (bindings, errors) = parse(r#"
+            next_text = "Space"
+            toggle_autoplay = ["Ctrl+A", "F5"]
+            toggle_mute = []
+        "#);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(bindings.get(InputAction::NextText), [combo("Space").unwrap()]);
+        assert_eq!(bindings.get(InputAction::ToggleAutoplay), [combo("Ctrl+A").unwrap(), combo("F5").unwrap()]);
+        assert!(bindings.get(InputAction::ToggleMute).is_empty());
+        // 書かなかった操作は既定のまま
+        assert_eq!(bindings.get(InputAction::BingoNext), [combo("B").unwrap()]);
+    }
+
+    #[test]
+    fn bad_entries_are_reported_one_by_one() {
+        let (bindings, errors) = parse(r#"
+            next_txt = "Space"
+            toggle_mute = "Hyper+M"
+            bingo_next = 5
+            stopwatch_lap = "C"
+        "#);
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors.contains(&"[keybindings] unknown action `next_txt`".to_string()));
+        assert!(errors.iter().any(|e| e.starts_with("[keybindings] toggle_mute: unknown modifier `Hyper`")), "{:?}", errors);
+        assert!(errors.iter().any(|e| e.starts_with("[keybindings] bingo_next: ")), "{:?}", errors);
+        assert!(errors.contains(&"[keybindings] `C` is bound to both countdown_normal and stopwatch_lap".to_string()));
+
+        // 読めなかった項目は既定のまま
+        assert_eq!(bindings.get(InputAction::ToggleMute), [combo("M").unwrap()]);
+        assert_eq!(bindings.get(InputAction::BingoNext), [combo("B").unwrap()]);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::loader::TextSource;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Ja,
+    En,
+    Zh,
+    Ko,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [Language::Ja, Language::En, Language::Zh, Language::Ko];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::Ja => "ja",
+            Language::En => "en",
+            Language::Zh => "zh",
+            Language::Ko => "ko",
+        }
+    }
+}
+
+// 翻訳付きテキストの見せ方（プリセットごとに指定）
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageMode {
+    #[default]
+    Primary, // content（日本語）のみ
+    Cycle,   // 1つのテキストを言語ごとに順に表示してから次へ進む
+    PerPass, // プリセット1周ごとに言語を切り替える
+    Concat,  // 全言語を並べて1回で表示する
+}
+
+// 表示する言語を決める。pass はプリセットを何周したか、step は同じテキストを何回表示したか
+pub fn select_languages(text: &TextSource, mode: LanguageMode, pass: usize, step: usize) -> Vec<Language> {
+    let available = text.languages();
+    match mode {
+        LanguageMode::Primary => vec![Language::Ja],
+        LanguageMode::Cycle => vec![available[step % available.len()]],
+        LanguageMode::PerPass => vec![available[pass % available.len()]],
+        LanguageMode::Concat => available,
+    }
+}
+
+// 表示中の言語をレスポンス用の文字列にする（複数なら "ja+en"）
+pub fn language_label(languages: &[Language]) -> String {
+    languages.iter().map(Language::code).collect::<Vec<_>>().join("+")
+}
@@ -0,0 +1,51 @@
+use crate::loader::LedConfig;
+use bevy::{
+    camera::{visibility::RenderLayers, RenderTarget},
+    image::ImageSampler,
+    prelude::*,
+    render::render_resource::TextureFormat,
+};
+
+// LEDパネル表示用のレイヤー（メインのテキストとは別に描画する）
+const LED_DISPLAY_LAYER: usize = 1;
+
+// パネルの実解像度のオフスクリーン画像を作り、それをウィンドウ上に
+// 最近傍補間で拡大表示するカメラとUIノードをスポーンする。
+// 戻り値はボード用カメラの描画先。
+pub fn setup_led_output(
+    cmds: &mut Commands,
+    images: &mut Assets<Image>,
+    led: &LedConfig,
+) -> RenderTarget {
+    let mut image = Image::new_target_texture(led.width, led.height, TextureFormat::Bgra8UnormSrgb);
+    image.sampler = ImageSampler::nearest();
+    let handle = images.add(image);
+
+    let display_camera = cmds.spawn((
+        Camera2d,
+        Camera {
+            order: 1,
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..default()
+        },
+        Msaa::Off,
+        RenderLayers::layer(LED_DISPLAY_LAYER),
+    )).id();
+
+    cmds.spawn((
+        ImageNode::new(handle.clone()),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(led.offset[0] as f32),
+            top: Val::Px(led.offset[1] as f32),
+            width: Val::Px((led.width * led.scale) as f32),
+            height: Val::Px((led.height * led.scale) as f32),
+            ..default()
+        },
+        UiTargetCamera(display_camera),
+    ));
+
+    println!("LED output: {}x{} (x{}) at {:?}", led.width, led.height, led.scale, led.offset);
+
+    RenderTarget::Image(handle.into())
+}
@@ -1,144 +1,1051 @@
+use crate::bingo::BingoVariant;
+use crate::countdown::{CountdownFormat, FinishAction};
+use crate::language::{Language, LanguageMode};
+use crate::transition::TransitionEffect;
 use bevy::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
 use std::error::Error;
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-#[derive(Serialize, Deserialize, Debug, Resource, Clone)]
+// 読み込みエラー。どのファイルの何行目・何列目で何が悪いかを持つ
+#[derive(Debug, Clone)]
+pub struct LoadError {
+    pub file: PathBuf,
+    pub line: Option<u64>,
+    pub column: Option<u64>,
+    pub reason: String,
+}
+
+impl LoadError {
+    pub fn new(file: &Path, line: Option<u64>, column: Option<u64>, reason: impl Into<String>) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            line,
+            column,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn io(file: &Path, e: std::io::Error) -> Self {
+        Self::new(file, None, None, e.to_string())
+    }
+
+    fn csv(file: &Path, e: &csv::Error, headers: Option<&csv::StringRecord>) -> Self {
+        let line = e.position().map(|pos| pos.line());
+        match e.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => {
+                let column = err.field().map(|field| field + 1);
+                let header = err.field().and_then(|field| headers?.get(field as usize));
+                let reason = match header {
+                    Some(header) => format!("`{}`: {}", header, err.kind()),
+                    None => err.kind().to_string(),
+                };
+                Self::new(file, line, column, reason)
+            }
+            csv::ErrorKind::UnequalLengths { expected_len, len, .. } => {
+                Self::new(file, line, None, format!("expected {} fields, found {}", expected_len, len))
+            }
+            _ => Self::new(file, line, None, e.to_string()),
+        }
+    }
+
+    fn json(file: &Path, e: &serde_json::Error) -> Self {
+        Self::new(file, Some(e.line() as u64), Some(e.column() as u64), strip_location(&e.to_string()))
+    }
+
+    fn yaml(file: &Path, e: &serde_norway::Error) -> Self {
+        let location = e.location();
+        Self::new(
+            file,
+            location.as_ref().map(|l| l.line() as u64),
+            location.as_ref().map(|l| l.column() as u64),
+            strip_location(&e.to_string()),
+        )
+    }
+
+    fn toml(file: &Path, content: &str, e: &toml::de::Error) -> Self {
+        // バイト位置から行・列を求める
+        let (line, column) = match e.span() {
+            Some(span) => {
+                let before = &content[..span.start];
+                let line = before.matches('\n').count() as u64 + 1;
+                let column = before.rsplit('\n').next().unwrap_or("").chars().count() as u64 + 1;
+                (Some(line), Some(column))
+            }
+            None => (None, None),
+        };
+        Self::new(file, line, column, e.message())
+    }
+}
+
+// serde_json / serde_norway のメッセージ末尾の " at line X column Y" を取り除く（行・列は別に持つ）
+fn strip_location(message: &str) -> String {
+    match message.rsplit_once(" at line ") {
+        Some((reason, _)) => reason.to_string(),
+        None => message.to_string(),
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+impl Error for LoadError {}
+
+// 起動時に発生した読み込みエラー（画面にも表示する）
+#[derive(Resource, Default)]
+pub struct LoadDiagnostics {
+    pub errors: Vec<LoadError>,
+    pub quiet: bool, // 標準出力に何も書かない（export の出力に混ざらないように）
+}
+
+impl LoadDiagnostics {
+    pub fn push(&mut self, e: LoadError) {
+        self.log(format_args!("Err: {}", e));
+        self.errors.push(e);
+    }
+
+    // 読み込みの経過を表示する
+    pub fn log(&self, message: impl fmt::Display) {
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
+}
+
+// 設定ファイルとプリセットの置き場所（既定は ~/ebb と ~/ebb/presets）
+#[derive(Resource, Debug, Clone)]
+pub struct EbbPaths {
+    pub config_dir: PathBuf,
+    pub preset_dir: PathBuf,
+}
+
+impl EbbPaths {
+    pub fn new(config_dir: Option<PathBuf>, preset_dir: Option<PathBuf>) -> Self {
+        let config_dir = config_dir
+            .unwrap_or_else(|| std::env::home_dir().unwrap_or_default().join("ebb"));
+        let preset_dir = preset_dir.unwrap_or_else(|| config_dir.join("presets"));
+        Self { config_dir, preset_dir }
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join("config.toml")
+    }
+
+    pub fn snippet_file(&self) -> PathBuf {
+        self.config_dir.join("snippets.toml")
+    }
+
+    // プリセットディレクトリ内のプリセットファイル一覧（名前順）
+    pub fn preset_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.preset_dir)? {
+            let path = entry?.path();
+            if PresetFormat::from_path(&path).is_some() && !is_meta_file(&path) {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Resource, Clone, Default)]
 pub struct TextSource {
     pub content: String,
+    #[serde(deserialize_with = "deserialize_duration")]
     pub duration: f32,
+    // 翻訳（CSV では en / zh / ko 列）。content は日本語として扱う
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub en: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zh: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ko: Option<String>,
+    // このテキストに入るときの効果（省略時は config.toml の [transition]）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition: Option<TransitionEffect>,
+    // true なら表示するときにチャイムを鳴らす（緊急のお知らせ用）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chime: Option<bool>,
+    // CSV の何行目か（@include のエラーの報告用）
+    #[serde(skip)]
+    pub line: Option<u64>,
+}
+
+impl TextSource {
+    pub fn translation(&self, language: Language) -> Option<&str> {
+        let text = match language {
+            Language::Ja => Some(&self.content),
+            Language::En => self.en.as_ref(),
+            Language::Zh => self.zh.as_ref(),
+            Language::Ko => self.ko.as_ref(),
+        };
+        text.map(String::as_str).filter(|text| !text.is_empty())
+    }
+
+    // 中身のある言語の一覧（ja は常に含む）
+    pub fn languages(&self) -> Vec<Language> {
+        Language::ALL.into_iter()
+            .filter(|language| *language == Language::Ja || self.translation(*language).is_some())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresetFormat {
+    Csv,
+    Toml,
+    Json,
+    Yaml,
+}
+
+const META_SUFFIX: &str = ".meta.toml";
+
+fn is_meta_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(META_SUFFIX))
+}
+
+impl PresetFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+// プリセットの表示名や並び順など。CSV の場合は同名の .meta.toml に書く
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PresetMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "is_default_language_mode")]
+    pub language_mode: LanguageMode,
 }
 
-#[derive(Deserialize, Debug, Resource, Default)]
+fn is_default_language_mode(mode: &LanguageMode) -> bool {
+    *mode == LanguageMode::default()
+}
+
+// TOML / JSON / YAML のプリセットファイル。texts に CSV と同じ形式で並べる
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PresetFile {
+    #[serde(flatten)]
+    pub meta: PresetMeta,
+    pub texts: Vec<TextSource>,
+}
+
+// config.toml。省略した項目は Default の値になる
+#[derive(Deserialize, Debug, Resource)]
+#[serde(default)]
 pub struct Config {
     pub text_size: f32,
-    pub window_width: f32,
     pub camera_offset: f32,
+    pub window: WindowConfig,
+    pub led: Option<LedConfig>, // 指定するとLEDマトリクス出力モードになる
+    pub fonts: FontConfig,
+    pub transition: TransitionConfig,
+    pub bingo: BingoConfig,
+    pub countdown: CountdownConfig,
+    pub stopwatch: StopwatchConfig,
+    pub autoplay: AutoplayConfig,
+    pub sound: SoundConfig,
+    // 操作の割り当ては keybindings.rs・gamepad.rs・midi.rs で1項目ずつ読む。
+    // 書き間違えた項目だけを読み込みエラーにして、残りの設定は使えるようにする
+    pub keybindings: toml::Table,  // 指定しなかった操作は既定のキー
+    pub gamepad: toml::Table,      // 指定しなかった操作は既定のボタン
+    pub midi: Option<toml::Table>, // 指定すると MIDI コントローラーから操作できる
+}
+
+// [sound] テーブル。効果音のファイル（config ディレクトリからの相対パス、Ogg Vorbis）。省略した音は鳴らさない
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SoundConfig {
+    pub volume: f32, // 0.0～1.0
+    pub muted: bool,
+    pub tick: Option<PathBuf>,
+    pub buzzer: Option<PathBuf>,
+    pub drumroll: Option<PathBuf>,
+    pub reveal: Option<PathBuf>,
+    pub chime: Option<PathBuf>,
+}
+
+impl Default for SoundConfig {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+            tick: None,
+            buzzer: None,
+            drumroll: None,
+            reveal: None,
+            chime: None,
+        }
+    }
+}
+
+// [autoplay] テーブル。自動再生で静止・ループのテキストを表示しておく時間（秒）
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AutoplayConfig {
+    pub hold: f32,
+}
+
+impl Default for AutoplayConfig {
+    fn default() -> Self {
+        Self { hold: 5.0 }
+    }
+}
+
+// [stopwatch] テーブル。ストップウォッチの表示
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StopwatchConfig {
+    pub format: CountdownFormat,
+    pub label: Option<String>,
+}
+
+impl Default for StopwatchConfig {
+    fn default() -> Self {
+        Self {
+            format: CountdownFormat::MmSs,
+            label: None,
+        }
+    }
+}
+
+// [countdown] テーブル。キー操作で始めるカウントダウンの表示（WebSocket では毎回指定できる）
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CountdownConfig {
+    pub format: CountdownFormat,
+    pub label: Option<String>,
+    pub until: Option<String>, // "15:00" のように書くと、起動時にその時刻までのカウントダウンを始める
+    pub on_finish: FinishAction,
+}
+
+// [bingo] テーブル。抽選の種類と範囲、ルーレットの演出時間（秒）
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BingoConfig {
+    pub variant: BingoVariant,
+    pub min: u32, // variant = "range" のときの範囲
+    pub max: u32,
+    pub exclude: Vec<u32>,             // 引かない番号
+    pub lottery_file: Option<PathBuf>, // variant = "lottery" で使う名前の CSV（config ディレクトリからの相対パス）
+    #[serde(deserialize_with = "crate::bingo::deserialize_seed")]
+    pub seed: Option<u64>,             // 引く順を決めるシード（省略時はランダム、10進の文字列でもよい）
+    pub spin_duration: f32,  // 数字が止まるまでの時間（0 ですぐ表示）
+    pub start_interval: f32, // 回り始めの数字の切り替え間隔
+    pub end_interval: f32,   // 止まる直前の切り替え間隔
+    pub reveal_fade: f32,    // 止まった番号のフェードイン時間
+}
+
+impl Default for BingoConfig {
+    fn default() -> Self {
+        Self {
+            variant: BingoVariant::Ball75,
+            min: 1,
+            max: 100,
+            exclude: Vec::new(),
+            lottery_file: None,
+            seed: None,
+            spin_duration: 3.0,
+            start_interval: 0.05,
+            end_interval: 0.4,
+            reveal_fade: 0.3,
+        }
+    }
+}
+
+// [transition] テーブル。テキストを切り替えるときの既定の効果
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct TransitionConfig {
+    pub effect: TransitionEffect,
+    pub duration: f32, // 秒
+}
+
+impl Default for TransitionConfig {
+    fn default() -> Self {
+        Self {
+            effect: TransitionEffect::Cut,
+            duration: 0.5,
+        }
+    }
+}
+
+impl TransitionConfig {
+    // テキストごとの指定があればそちらを使う
+    pub fn with_effect(&self, effect: Option<TransitionEffect>) -> Self {
+        Self {
+            effect: effect.unwrap_or(self.effect),
+            ..*self
+        }
+    }
+}
+
+// [fonts] テーブル。パスは assets フォルダからの相対パス
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FontConfig {
+    pub primary: String,
+    pub fallbacks: Vec<String>, // primary にない文字を前から順に探す
+    pub languages: HashMap<Language, String>, // 言語ごとのフォント（省略した言語は primary）
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            primary: "fonts/ipag.ttf".to_string(),
+            fallbacks: Vec::new(),
+            languages: HashMap::new(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            text_size: 1080.0,
+            camera_offset: 0.0,
+            window: WindowConfig::default(),
+            led: None,
+            fonts: FontConfig::default(),
+            transition: TransitionConfig::default(),
+            bingo: BingoConfig::default(),
+            countdown: CountdownConfig::default(),
+            stopwatch: StopwatchConfig::default(),
+            autoplay: AutoplayConfig::default(),
+            sound: SoundConfig::default(),
+            keybindings: toml::Table::new(),
+            gamepad: toml::Table::new(),
+            midi: None,
+        }
+    }
+}
+
+// [led] テーブル。パネルの実解像度で描画し、ウィンドウ上の offset の位置に scale 倍で表示する
+#[derive(Deserialize, Debug, Clone)]
+pub struct LedConfig {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default = "default_led_scale")]
+    pub scale: u32,
+    #[serde(default)]
+    pub offset: [i32; 2],
+}
+
+fn default_led_scale() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+// [window] テーブル。省略した項目は Default の値になる
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub mode: WindowModeSetting,
+    pub monitor: Option<usize>, // 表示先モニターの番号（省略時はプライマリ）
+    pub width: u32,
+    pub height: u32,
+    pub position: Option<[i32; 2]>, // 省略時はモニター中央
+    pub hide_cursor: bool,
+    pub vsync: bool,
+    pub always_on_top: bool,
 }
 
-#[derive(Resource)]
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            mode: WindowModeSetting::Windowed,
+            monitor: None,
+            width: 1920,
+            height: 1080,
+            position: None,
+            hide_cursor: false,
+            vsync: true,
+            always_on_top: false,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
 pub struct PresetManager {
     pub presets: HashMap<String, Vec<TextSource>>,
+    pub meta: HashMap<String, PresetMeta>,
+}
+
+impl PresetManager {
+    pub fn meta(&self, name: &str) -> PresetMeta {
+        self.meta.get(name).cloned().unwrap_or_default()
+    }
+
+    // order 順（未指定は後ろ）、同じなら名前順
+    pub fn sorted_names(&self) -> Vec<String> {
+        let mut names: Vec<(i32, &String)> = self.presets.keys()
+            .map(|name| (self.meta.get(name).and_then(|meta| meta.order).unwrap_or(i32::MAX), name))
+            .collect();
+        names.sort();
+        names.into_iter().map(|(_, name)| name.clone()).collect()
+    }
+
+    // default = true のプリセット、なければ並び順で最初のもの
+    pub fn default_name(&self) -> Option<String> {
+        let names = self.sorted_names();
+        names.iter()
+            .find(|name| self.meta(name).default)
+            .or_else(|| names.first())
+            .cloned()
+    }
 }
 
-pub fn load_csv(file: &str) -> Result<Vec<TextSource>, Box<dyn Error>> {
-    let mut csv_path = std::env::home_dir().unwrap();
-    csv_path.push("ebb/presets/".to_string() + file);
-    let file_content = std::fs::read_to_string(csv_path)?;
+pub fn load_csv(csv_path: &Path) -> Result<Vec<TextSource>, LoadError> {
+    let file_content = std::fs::read_to_string(csv_path).map_err(|e| LoadError::io(csv_path, e))?;
 
-    let rdr = csv::ReaderBuilder::new()
+    let mut rdr = csv::ReaderBuilder::new()
         .has_headers(true)
         .from_reader(file_content.as_bytes());
 
-    let result: Vec<TextSource> = rdr
-        .into_deserialize()
-        .collect::<Result<Vec<TextSource>, csv::Error>>()?;
+    let headers = rdr.headers().map_err(|e| LoadError::csv(csv_path, &e, None))?.clone();
+    for required in ["content", "duration"] {
+        if !headers.iter().any(|header| header == required) {
+            return Err(LoadError::new(csv_path, Some(1), None, format!("missing header `{}`", required)));
+        }
+    }
+
+    let mut result = Vec::new();
+    for record in rdr.records() {
+        let record = record.map_err(|e| LoadError::csv(csv_path, &e, Some(&headers)))?;
+        let line = record.position().map(|pos| pos.line());
+        let mut text: TextSource = record.deserialize(Some(&headers)).map_err(|e| {
+            let mut e = LoadError::csv(csv_path, &e, Some(&headers));
+            e.line = e.line.or(line);
+            // duration の検査などの独自のエラーには列がないので、メッセージの先頭の列名から求める
+            e.column = e.column.or_else(|| {
+                headers.iter()
+                    .position(|header| e.reason.starts_with(&format!("`{}`", header)))
+                    .map(|i| i as u64 + 1)
+            });
+            e
+        })?;
+        text.line = line;
+        result.push(text);
+    }
     Ok(result)
 }
 
-pub fn load_all_presets() -> Result<HashMap<String, Vec<TextSource>>, Box<dyn Error>> {
-    let mut presets_path = std::env::home_dir().unwrap();
-    presets_path.push("ebb/presets");
-    
-    let mut presets = HashMap::new();
-    
-    if !presets_path.exists() {
-        println!("Warning: Presets directory not found at {:?}", presets_path);
-        return Ok(presets);
+// 抽選用の名前の一覧。name 列を上から読み、空の行は飛ばす
+pub fn load_lottery(csv_path: &Path) -> Result<Vec<String>, LoadError> {
+    let file_content = std::fs::read_to_string(csv_path).map_err(|e| LoadError::io(csv_path, e))?;
+
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(file_content.as_bytes());
+
+    let headers = rdr.headers().map_err(|e| LoadError::csv(csv_path, &e, None))?.clone();
+    let Some(column) = headers.iter().position(|header| header == "name") else {
+        return Err(LoadError::new(csv_path, Some(1), None, "missing header `name`"));
+    };
+
+    let mut names = Vec::new();
+    for record in rdr.records() {
+        let record = record.map_err(|e| LoadError::csv(csv_path, &e, Some(&headers)))?;
+        let name = record.get(column).unwrap_or_default().trim();
+        if !name.is_empty() {
+            names.push(name.to_string());
+        }
     }
+    if names.is_empty() {
+        return Err(LoadError::new(csv_path, None, None, "no names to draw"));
+    }
+    Ok(names)
+}
+
+// duration は読み込むときに確かめる。どの形式でもパーサーが行・列を付けて報告する
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let duration = f32::deserialize(deserializer)?;
+    if duration >= 0.0 && duration.is_finite() {
+        Ok(duration)
+    } else {
+        Err(de::Error::custom("`duration` must be 0 or a positive number"))
+    }
+}
+
+// 拡張子に応じてプリセットファイルを読み込む
+pub fn load_preset(path: &Path) -> Result<PresetFile, LoadError> {
+    let format = PresetFormat::from_path(path)
+        .ok_or_else(|| LoadError::new(path, None, None, "unsupported preset file type"))?;
+    let read = || std::fs::read_to_string(path).map_err(|e| LoadError::io(path, e));
+    match format {
+        PresetFormat::Csv => Ok(PresetFile {
+            meta: load_meta(path)?,
+            texts: load_csv(path)?,
+        }),
+        PresetFormat::Toml => {
+            let file_content = read()?;
+            toml::from_str(&file_content).map_err(|e| LoadError::toml(path, &file_content, &e))
+        }
+        PresetFormat::Json => serde_json::from_str(&read()?).map_err(|e| LoadError::json(path, &e)),
+        PresetFormat::Yaml => serde_norway::from_str(&read()?).map_err(|e| LoadError::yaml(path, &e)),
+    }
+}
+
+// CSV プリセットのメタデータ（foo.csv に対する foo.meta.toml）。なければ既定値
+fn load_meta(csv_path: &Path) -> Result<PresetMeta, LoadError> {
+    let Some(stem) = csv_path.file_stem().and_then(|stem| stem.to_str()) else {
+        return Ok(PresetMeta::default());
+    };
+    let meta_path = csv_path.with_file_name(format!("{}{}", stem, META_SUFFIX));
+    if !meta_path.exists() {
+        return Ok(PresetMeta::default());
+    }
+    let file_content = std::fs::read_to_string(&meta_path).map_err(|e| LoadError::io(&meta_path, e))?;
+    toml::from_str(&file_content).map_err(|e| LoadError::toml(&meta_path, &file_content, &e))
+}
+
+// プリセットを指定した形式の文字列に書き出す（CSV にはメタデータは含まれない）
+pub fn export_preset(preset: &PresetFile, format: PresetFormat) -> Result<String, Box<dyn Error>> {
+    let output = match format {
+        PresetFormat::Csv => {
+            // 翻訳列・transition 列・chime 列は空でも常に出力して列数をそろえる
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(["content", "duration", "en", "zh", "ko", "transition", "chime"])?;
+            for text in &preset.texts {
+                writer.write_record([
+                    text.content.as_str(),
+                    &text.duration.to_string(),
+                    text.en.as_deref().unwrap_or_default(),
+                    text.zh.as_deref().unwrap_or_default(),
+                    text.ko.as_deref().unwrap_or_default(),
+                    text.transition.map(|effect| effect.code()).unwrap_or_default(),
+                    text.chime.map(|chime| if chime { "true" } else { "false" }).unwrap_or_default(),
+                ])?;
+            }
+            String::from_utf8(writer.into_inner()?)?
+        }
+        PresetFormat::Toml => toml::to_string_pretty(preset)?,
+        PresetFormat::Json => serde_json::to_string_pretty(preset)? + "\n",
+        PresetFormat::Yaml => serde_norway::to_string(preset)?,
+    };
+    Ok(output)
+}
+
+pub fn load_all_presets(paths: &EbbPaths, diagnostics: &mut LoadDiagnostics) -> PresetManager {
+    let mut manager = PresetManager::default();
+    let mut sources: HashMap<String, PathBuf> = HashMap::new();
     
-    let entries = std::fs::read_dir(&presets_path)?;
-    
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if let Some(extension) = path.extension() {
-            if extension == "csv" {
-                if let Some(file_name) = path.file_stem() {
-                    if let Some(preset_name) = file_name.to_str() {
-                        match load_csv(&format!("{}.csv", preset_name)) {
-                            Ok(texts) => {
-                                println!("Loaded preset '{}' with {} texts", preset_name, texts.len());
-                                presets.insert(preset_name.to_string(), texts);
-                            }
-                            Err(e) => {
-                                println!("Failed to load preset '{}': {}", preset_name, e);
-                            }
+    if !paths.preset_dir.exists() {
+        diagnostics.log(format_args!("Warning: Presets directory not found at {:?}", paths.preset_dir));
+    } else {
+        match paths.preset_files() {
+            Ok(files) => {
+                for path in files {
+                    let Some(preset_name) = path.file_stem().and_then(|name| name.to_str()) else {
+                        continue;
+                    };
+                    // 同じ名前のプリセットが別形式で既にある場合は後のものを無視する
+                    if let Some(existing) = sources.get(preset_name) {
+                        diagnostics.push(LoadError::new(
+                            &path,
+                            None,
+                            None,
+                            format!("preset name '{}' is already used by {}", preset_name, existing.display()),
+                        ));
+                        continue;
+                    }
+                    sources.insert(preset_name.to_string(), path.clone());
+                    match load_preset(&path) {
+                        Ok(preset) => {
+                            diagnostics.log(format_args!("Loaded preset '{}' with {} texts", preset_name, preset.texts.len()));
+                            manager.presets.insert(preset_name.to_string(), preset.texts);
+                            manager.meta.insert(preset_name.to_string(), preset.meta);
                         }
+                        Err(e) => diagnostics.push(e),
                     }
                 }
             }
+            Err(e) => diagnostics.push(LoadError::io(&paths.preset_dir, e)),
         }
     }
+
+    let snippets = load_snippets(paths, diagnostics);
+    expand_includes(&mut manager, snippets, &sources, &paths.snippet_file(), diagnostics);
+
+    let defaults: Vec<&String> = manager.meta.iter()
+        .filter(|(_, meta)| meta.default)
+        .map(|(name, _)| name)
+        .collect();
+    if defaults.len() > 1 {
+        diagnostics.push(LoadError::new(
+            &paths.preset_dir,
+            None,
+            None,
+            format!("{} presets are marked default, using '{}'", defaults.len(), manager.default_name().unwrap_or_default()),
+        ));
+    }
     
     // デフォルトプリセットがない場合は追加
-    if presets.is_empty() {
-        println!("No presets found, adding default preset");
-        presets.insert("default".to_string(), vec![
+    if manager.presets.is_empty() {
+        diagnostics.log("No presets found, adding default preset");
+        manager.presets.insert("default".to_string(), vec![
             TextSource {
                 content: "Default Demo Text 1".to_string(),
                 duration: 5.0,
+                ..default()
             },
             TextSource {
                 content: "Default Demo Text 2".to_string(),
                 duration: 5.0,
+                ..default()
             },
         ]);
     }
     
-    Ok(presets)
+    manager
 }
 
-pub fn load_config() -> Result<Config, Box<dyn Error>> {
-    let mut conf_path = std::env::home_dir().unwrap();
-    conf_path.push("ebb/config.toml");
-    let file_content = std::fs::read_to_string(conf_path).unwrap();
-    println!("{}", file_content);
-    let result: Config = toml::from_str(&file_content.as_str())?;
-    Ok(result)
+// snippets.toml の共通メッセージ。[[名前]] ごとに texts と同じ形式で並べる
+fn load_snippets(paths: &EbbPaths, diagnostics: &mut LoadDiagnostics) -> HashMap<String, Vec<TextSource>> {
+    let snippet_path = paths.snippet_file();
+    if !snippet_path.exists() {
+        return HashMap::new();
+    }
+    let result = std::fs::read_to_string(&snippet_path)
+        .map_err(|e| LoadError::io(&snippet_path, e))
+        .and_then(|content| {
+            toml::from_str(&content).map_err(|e| LoadError::toml(&snippet_path, &content, &e))
+        });
+    match result {
+        Ok(snippets) => snippets,
+        Err(e) => {
+            diagnostics.push(e);
+            HashMap::new()
+        }
+    }
 }
 
-pub fn unwrap_csv(f: &str) -> Vec<TextSource> {
-    match load_csv(f) {
-        Ok(n) => return n,
-        Err(_e) => {
-            println!("Err: Can't Load Preset File: {}", f);
-            return vec![TextSource {
-                content: "This is a Demo Text".to_string(),
-                duration: 5.0,
-            }];
+const INCLUDE_PREFIX: &str = "@include ";
+
+fn include_target(text: &TextSource) -> Option<&str> {
+    text.content.trim().strip_prefix(INCLUDE_PREFIX).map(str::trim)
+}
+
+// "@include 名前" の行を、同名のプリセットまたはスニペットの中身で置き換える
+fn expand_includes(
+    manager: &mut PresetManager,
+    snippets: HashMap<String, Vec<TextSource>>,
+    sources: &HashMap<String, PathBuf>,
+    snippet_file: &Path,
+    diagnostics: &mut LoadDiagnostics,
+) {
+    let mut sources = sources.clone();
+    let mut raw = manager.presets.clone();
+    for (name, texts) in snippets {
+        if let Some(preset_file) = sources.get(&name) {
+            diagnostics.push(LoadError::new(
+                snippet_file,
+                None,
+                None,
+                format!("snippet '{}' has the same name as {}, the preset is used", name, preset_file.display()),
+            ));
+            continue;
         }
-    };
+        sources.insert(name.clone(), snippet_file.to_path_buf());
+        raw.insert(name, texts);
+    }
+
+    let mut errors: Vec<LoadError> = Vec::new();
+    let mut expanded = HashMap::new();
+    for (name, texts) in manager.presets.iter_mut() {
+        if texts.iter().any(|text| include_target(text).is_some()) {
+            let mut stack = vec![name.clone()];
+            *texts = expand_preset(name, &raw, &sources, &mut stack, &mut expanded, &mut errors).0;
+        }
+    }
+
+    // 循環は関わるプリセットごとに見つかるので、同じエラーは一度だけ報告する
+    let mut reported = std::collections::HashSet::new();
+    for e in errors {
+        if reported.insert(e.to_string()) {
+            diagnostics.push(e);
+        }
+    }
+}
+
+// include の行のエラー。CSV は行番号、それ以外は何件目か（1始まり）で場所を示す
+fn include_error(file: &Path, text: &TextSource, row: usize, reason: String) -> LoadError {
+    match text.line {
+        Some(line) => LoadError::new(file, Some(line), None, reason),
+        None => LoadError::new(file, None, None, format!("row {}: {}", row + 1, reason)),
+    }
+}
+
+// 展開したプリセット・スニペットは expanded に控えて使い回す（同じものを何か所から include しても一度だけ展開する）。
+// 循環を途中で切った結果は辿り始めた場所で変わるので控えない（戻り値の bool は循環を切ったかどうか）
+fn expand_preset(
+    name: &str,
+    raw: &HashMap<String, Vec<TextSource>>,
+    sources: &HashMap<String, PathBuf>,
+    stack: &mut Vec<String>,
+    expanded: &mut HashMap<String, Vec<TextSource>>,
+    errors: &mut Vec<LoadError>,
+) -> (Vec<TextSource>, bool) {
+    if let Some(texts) = expanded.get(name) {
+        return (texts.clone(), false);
+    }
+    let file = sources.get(name).cloned().unwrap_or_default();
+    let mut result = Vec::new();
+    let mut cut = false;
+    for (row, text) in raw[name].iter().enumerate() {
+        let Some(target) = include_target(text) else {
+            result.push(text.clone());
+            continue;
+        };
+        if let Some(start) = stack.iter().position(|included| included == target) {
+            // 同じ循環がどこから辿っても同じ表記になるよう、名前順で最小のものから書き、
+            // 最小のものを include している行の場所で報告する
+            let mut cycle = stack[start..].to_vec();
+            let first = cycle.iter().enumerate().min_by_key(|(_, name)| *name).map_or(0, |(i, _)| i);
+            cycle.rotate_left(first);
+            let last = cycle[cycle.len() - 1].clone();
+            cycle.push(cycle[0].clone());
+            let reason = format!("include cycle: {}", cycle.join(" -> "));
+            let file = sources.get(&last).cloned().unwrap_or_default();
+            let closing = raw[&last].iter().enumerate().find(|(_, text)| include_target(text) == Some(cycle[0].as_str()));
+            errors.push(match closing {
+                Some((row, text)) => include_error(&file, text, row, reason),
+                None => LoadError::new(&file, None, None, reason),
+            });
+            cut = true;
+            continue;
+        }
+        if !raw.contains_key(target) {
+            errors.push(include_error(&file, text, row, format!("unknown include '{}'", target)));
+            continue;
+        }
+        stack.push(target.to_string());
+        let (texts, cut_inside) = expand_preset(target, raw, sources, stack, expanded, errors);
+        result.extend(texts);
+        cut |= cut_inside;
+        stack.pop();
+    }
+    if !cut {
+        expanded.insert(name.to_string(), result.clone());
+    }
+    (result, cut)
+}
+
+pub fn load_config(paths: &EbbPaths) -> Result<Config, LoadError> {
+    let conf_path = paths.config_file();
+    let file_content = std::fs::read_to_string(&conf_path).map_err(|e| LoadError::io(&conf_path, e))?;
+    toml::from_str(file_content.as_str()).map_err(|e| LoadError::toml(&conf_path, &file_content, &e))
 }
 
-pub fn unwrap_all_presets() -> PresetManager {
-    match load_all_presets() {
-        Ok(presets) => PresetManager { presets },
+// 設定ファイルがなければ既定値、壊れていればエラーを記録して既定値を使う
+pub fn unwrap_conf(paths: &EbbPaths, diagnostics: &mut LoadDiagnostics) -> Config {
+    if !paths.config_file().exists() {
+        println!("Warning: {:?} not found, using default config", paths.config_file());
+        return Config::default();
+    }
+    match load_config(paths) {
+        Ok(n) => n,
         Err(e) => {
-            println!("Err: Can't Load Presets: {}", e);
-            let mut default_presets = HashMap::new();
-            default_presets.insert("default".to_string(), vec![
-                TextSource {
-                    content: "This is a Demo Text".to_string(),
-                    duration: 5.0,
-                }
-            ]);
-            PresetManager { presets: default_presets }
+            diagnostics.push(e);
+            Config::default()
         }
     }
 }
 
-pub fn unwrap_conf() -> Config {
-    match load_config() {
-        Ok(n) => return n,
-        Err(_e) => {
-            println!("Err: Can't Load Config File!");
-            return Config {
-                text_size: 1080.0,
-                window_width: 1920.0,
-                camera_offset: 0.0,
-            };
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // テストごとに空の作業ディレクトリを作る
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ebb-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("presets")).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, content: &str) {
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn csv_error_has_line_and_column() {
+        let dir = test_dir("csv-error");
+        let path = dir.join("a.csv");
+        write(&path, "content,duration\nok,5\nbad,-1\n");
+        let e = load_csv(&path).unwrap_err();
+        assert_eq!((e.line, e.column), (Some(3), Some(2)));
+        assert!(e.reason.starts_with("`duration`"), "{}", e.reason);
+        assert!(e.to_string().ends_with(&format!("a.csv:3:2: {}", e.reason)));
+
+        write(&path, "content\nok\n");
+        let e = load_csv(&path).unwrap_err();
+        assert_eq!((e.line, e.column, e.reason.as_str()), (Some(1), None, "missing header `duration`"));
+    }
+
+    #[test]
+    fn structured_errors_have_line_and_column() {
+        let dir = test_dir("structured-error");
+
+        let path = dir.join("a.json");
+        write(&path, "{\n  \"texts\": [\n    { \"content\": \"x\", \"duration\": -1 }\n  ]\n}\n");
+        let e = load_preset(&path).unwrap_err();
+        assert_eq!(e.line, Some(3));
+        assert!(e.column.is_some());
+        assert!(!e.reason.contains(" at line "), "{}", e.reason);
+
+        let path = dir.join("b.toml");
+        write(&path, "title = \"B\"\n\n[[texts]]\ncontent = \"x\"\nduration = \"five\"\n");
+        let e = load_preset(&path).unwrap_err();
+        assert_eq!((e.line, e.column), (Some(5), Some(12)));
+
+        let path = dir.join("c.yaml");
+        write(&path, "texts:\n  - content: x\n    duration: -1\n");
+        let e = load_preset(&path).unwrap_err();
+        assert!(e.line.is_some());
+        assert!(e.column.is_some());
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let dir = test_dir("export");
+        let preset = PresetFile {
+            meta: PresetMeta {
+                title: Some("Opening".to_string()),
+                order: Some(2),
+                default: true,
+                tags: vec!["stage".to_string()],
+                description: Some("before the show".to_string()),
+                language_mode: LanguageMode::Cycle,
+            },
+            texts: vec![
+                TextSource {
+                    content: "ようこそ, \"ebb\"".to_string(),
+                    duration: 5.5,
+                    en: Some("Welcome".to_string()),
+                    transition: Some(TransitionEffect::Crossfade),
+                    chime: Some(true),
+                    ..default()
+                },
+                TextSource {
+                    content: "開演まで\nお待ちください".to_string(),
+                    duration: 0.0,
+                    ko: Some("잠시만".to_string()),
+                    ..default()
+                },
+            ],
+        };
+        let expected = serde_json::to_value(&preset).unwrap();
+
+        for (format, file) in [
+            (PresetFormat::Toml, "p.toml"),
+            (PresetFormat::Json, "p.json"),
+            (PresetFormat::Yaml, "p.yaml"),
+            (PresetFormat::Csv, "p.csv"),
+        ] {
+            let path = dir.join(file);
+            write(&path, &export_preset(&preset, format).unwrap());
+            let loaded = serde_json::to_value(load_preset(&path).unwrap()).unwrap();
+            if format == PresetFormat::Csv {
+                // CSV にはメタデータが入らない
+                assert_eq!(loaded["texts"], expected["texts"], "{}", file);
+                assert_eq!(loaded.as_object().unwrap().len(), 1, "{}", file);
+            } else {
+                assert_eq!(loaded, expected, "{}", file);
+            }
         }
-    };
+    }
+
+    fn contents(texts: &[TextSource]) -> Vec<&str> {
+        texts.iter().map(|text| text.content.as_str()).collect()
+    }
+
+    fn load(dir: &Path) -> (PresetManager, Vec<String>) {
+        let paths = EbbPaths::new(Some(dir.to_path_buf()), None);
+        let mut diagnostics = LoadDiagnostics { quiet: true, ..default() };
+        let manager = load_all_presets(&paths, &mut diagnostics);
+        let errors = diagnostics.errors.iter()
+            .map(|e| format!("{}:{}: {}", e.file.file_name().unwrap().to_string_lossy(), e.line.unwrap_or(0), e.reason))
+            .collect();
+        (manager, errors)
+    }
+
+    #[test]
+    fn includes_expand_presets_and_snippets() {
+        let dir = test_dir("include");
+        write(&dir.join("presets/main.csv"), "content,duration\nhello,5\n@include notice,0\n@include common,0\nbye,5\n");
+        write(&dir.join("presets/common.csv"), "content,duration\nshared,3\n@include notice,0\n");
+        write(&dir.join("snippets.toml"), "[[notice]]\ncontent = \"no smoking\"\nduration = 4\n");
+
+        let (manager, errors) = load(&dir);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            contents(&manager.presets["main"]),
+            ["hello", "no smoking", "shared", "no smoking", "bye"],
+        );
+        assert_eq!(contents(&manager.presets["common"]), ["shared", "no smoking"]);
+        assert!(!manager.presets.contains_key("notice"));
+    }
+
+    #[test]
+    fn include_cycle_is_reported_once() {
+        let dir = test_dir("include-cycle");
+        write(&dir.join("presets/a.csv"), "content,duration\none,5\n@include b,0\n");
+        write(&dir.join("presets/b.csv"), "content,duration\ntwo,5\n@include a,0\n");
+
+        let (manager, errors) = load(&dir);
+        assert_eq!(errors, ["b.csv:3: include cycle: a -> b -> a"]);
+        assert_eq!(contents(&manager.presets["a"]), ["one", "two"]);
+        assert_eq!(contents(&manager.presets["b"]), ["two", "one"]);
+    }
+
+    #[test]
+    fn unknown_include_is_reported() {
+        let dir = test_dir("include-unknown");
+        write(&dir.join("presets/a.toml"), "[[texts]]\ncontent = \"one\"\nduration = 5\n\n[[texts]]\ncontent = \"@include nope\"\nduration = 0\n");
+
+        let (manager, errors) = load(&dir);
+        assert_eq!(errors, ["a.toml:0: row 2: unknown include 'nope'"]);
+        assert_eq!(contents(&manager.presets["a"]), ["one"]);
+    }
 }
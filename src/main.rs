@@ -1,35 +1,98 @@
 use bevy::{
-    color::palettes::tailwind::SLATE_900,  prelude::*, text::TextLayoutInfo
+    camera::RenderTarget, color::palettes::tailwind::SLATE_900,  prelude::*, text::{FontSmoothing, TextLayoutInfo},
+    window::{CursorOptions, MonitorSelection, PresentMode, VideoModeSelection, WindowLevel, WindowMode, WindowPosition},
 };
 use bevy_tokio_tasks::TokioTasksPlugin;
+use clap::Parser;
+use std::process::ExitCode;
 
+mod action;
 mod bingo;
+mod cli;
+mod led;
 mod loader;
+mod midi;
+mod preview;
 mod server;
+mod sound;
+mod stopwatch;
 mod text;
 mod text_spawner;
+mod transition;
 mod countdown;
+mod diagnostics;
+mod gamepad;
+mod keybindings;
+mod language;
 
-use loader::{Config, TextSource, PresetManager};
-use bingo::BingoState;
+use cli::{Cli, Command};
+use loader::{Config, EbbPaths, LoadDiagnostics, TextSource, PresetManager, WindowConfig, WindowModeSetting};
 use countdown::CountdownTimer;
+use language::{Language, LanguageMode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use text_spawner::{FontData, GlyphCoverage, TextSegment};
 
-fn main() {
-    let preset_manager: PresetManager = loader::unwrap_all_presets();
-    let conf: Config = loader::unwrap_conf();
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let paths = EbbPaths::new(cli.config_dir, cli.preset_dir);
+
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run(paths),
+        Command::Validate => cli::validate(&paths),
+        Command::ListPresets => cli::list_presets(&paths),
+        Command::Send { json, url } => cli::send(&url, &json),
+        Command::Export { preset, format, output, force } => cli::export(&paths, &preset, format, output.as_deref(), force),
+        Command::Preview { preset, out_dir, frames } => preview::run(&paths, &preset, out_dir, frames),
+        #[cfg(feature = "midi")]
+        Command::MidiPorts => midi::list_ports(),
+    }
+}
+
+fn run(paths: EbbPaths) -> ExitCode {
+    let mut diagnostics = LoadDiagnostics::default();
+    let preset_manager: PresetManager = loader::load_all_presets(&paths, &mut diagnostics);
+    let conf: Config = loader::unwrap_conf(&paths, &mut diagnostics);
     
-    // デフォルトのプリセット（最初に見つかったもの、またはdefault）を取得
-    let default_preset_name = preset_manager.presets.keys().next().cloned()
+    // デフォルトのプリセット（default 指定のもの、なければ並び順で最初のもの）を取得
+    let default_preset_name = preset_manager.default_name()
         .unwrap_or_else(|| "default".to_string());
     let default_texts = preset_manager.presets.get(&default_preset_name)
         .cloned()
         .unwrap_or_else(|| vec![TextSource {
             content: "No presets available".to_string(),
             duration: 5.0,
+            ..default()
         }]);
+    let default_language_mode = preset_manager.meta(&default_preset_name).language_mode;
+    let bingo_state = bingo::new_game(&paths, &conf.bingo, &mut diagnostics);
+    let key_bindings = keybindings::KeyBindings::load(&paths, &conf, &mut diagnostics);
+    let gamepad_bindings = gamepad::GamepadBindings::load(&paths, &conf, &mut diagnostics);
+    let midi = midi::load(&paths, &conf, &mut diagnostics);
+    #[cfg(feature = "midi")]
+    let midi = midi.and_then(|(config, bindings)| Some((midi::connect(&paths, &config, &mut diagnostics)?, bindings)));
+    #[cfg(not(feature = "midi"))]
+    if midi.is_some() {
+        diagnostics.push(loader::LoadError::new(&paths.config_file(), None, None, "[midi] needs a build with the `midi` feature"));
+    }
+
+    // 15秒、減速モード（デフォルト）
+    let mut countdown_timer = CountdownTimer::new(15.0, countdown::CountdownMode::Decelerated);
+    if let Some(until) = &conf.countdown.until {
+        // 時刻を過ぎていれば始めない（再起動したときもその時刻までの残りから続く）
+        match countdown::parse_target(until, false) {
+            Some(target) => {
+                countdown_timer.format = conf.countdown.format;
+                countdown_timer.label = conf.countdown.label.clone();
+                countdown_timer.on_finish = conf.countdown.on_finish.clone();
+                countdown_timer.start_until(target);
+            }
+            None => println!("Countdown target {} is not a valid future time today, skipping", until),
+        }
+    }
     
     let mut app = App::new();
-    app.add_plugins(DefaultPlugins)
+    app.add_plugins(DefaultPlugins.set(window_plugin(&conf.window)))
         .add_plugins(TokioTasksPlugin::default())
         .insert_resource(ClearColor(Color::Srgba(SLATE_900)))
         .insert_resource(preset_manager)
@@ -37,33 +100,235 @@ fn main() {
             texts: default_texts,
             current_index: 0,
             current_preset: default_preset_name,
+            language_mode: default_language_mode,
+            pass: 0,
+            language_step: 0,
         })
         .insert_resource(conf)
+        .insert_resource(paths)
+        .insert_resource(diagnostics)
         .init_resource::<ScrollingState>()
         .init_resource::<ScrollingSpeed>()
+        .init_resource::<Viewport>()
         .init_resource::<Fonts>()
-        .init_resource::<BingoState>()
-        .insert_resource(CountdownTimer::new(15.0, countdown::CountdownMode::Decelerated)) // 15秒、減速モード（デフォルト）
+        .insert_resource(bingo_state)
+        .insert_resource(key_bindings)
+        .insert_resource(gamepad_bindings)
+        .init_resource::<keybindings::HelpOverlay>()
+        .insert_resource(countdown_timer)
+        .init_resource::<stopwatch::Stopwatch>()
+        .init_resource::<Autoplay>()
+        .init_resource::<countdown::ScreenFlash>()
+        .init_resource::<sound::Sounds>()
         .add_systems(Startup, setup)
+        .add_systems(Startup, stopwatch::setup_stopwatch)
+        .add_systems(Startup, sound::setup_sounds)
+        .add_systems(Update, (collect_font_data, resplit_showing_texts).chain())
+        .add_systems(Update, diagnostics::check_font_loads)
+        .add_systems(Update, diagnostics::show_load_diagnostics.after(diagnostics::check_font_loads))
+        .add_systems(Update, diagnostics::hide_load_diagnostics.before(handle_keyboard_action))
+        .add_systems(Update, update_viewport)
+        .add_systems(Update, start_pending_scroll.after(update_viewport))
         .add_systems(Update, text_scroll)
         .add_systems(Update, text_loop)
         .add_systems(Update, check_text_completion)
-        .add_systems(Update, handle_keyboard_action)
+        .add_systems(Update, handle_keyboard_action.before(action::ActionSet))
+        .add_systems(Update, gamepad::gamepad_input_system.before(action::ActionSet))
+        .add_systems(Update, gamepad::log_gamepads)
+        .add_systems(Update, autoplay_system.after(check_text_completion).before(action::ActionSet))
         .add_systems(Update, countdown::countdown_system)
-        .add_systems(Update, countdown::fade_system)
-        .add_systems(Update, countdown::countdown_finished_system);
+        .add_systems(Update, sound::sound_system)
+        .add_systems(Update, transition::fade_system.after(action::ActionSet))
+        .add_systems(Update, transition::transition_in_system.after(start_pending_scroll))
+        .add_systems(Update, transition::transition_out_system)
+        .add_systems(Update, bingo::roulette_system)
+        .add_observer(bingo::finish_roulette)
+        .add_systems(Update, stopwatch::stopwatch_system)
+        .add_systems(Update, countdown::countdown_finished_system.after(countdown::countdown_system).before(action::ActionSet))
+        .add_systems(Update, countdown::screen_flash_system)
+        .add_systems(Update, keybindings::help_overlay_system.after(action::ActionSet));
+
+    // MIDI コントローラー（つなげたときだけ）
+    #[cfg(feature = "midi")]
+    if let Some((midi_input, midi_bindings)) = midi {
+        app.insert_non_send_resource(midi_input)
+            .insert_resource(midi_bindings)
+            .add_systems(Update, midi::midi_input_system.before(action::ActionSet));
+    }
     
+    // 操作のメッセージと、それを処理するシステムをセットアップ
+    action::setup_actions(&mut app);
+
     // WebSocketサーバーをセットアップ
     server::setup_websocket_server(&mut app);
     
-    app.run();
+    match app.run() {
+        AppExit::Success => ExitCode::SUCCESS,
+        AppExit::Error(_) => ExitCode::FAILURE,
+    }
+}
+
+// config.toml の [window] からメインウィンドウの設定を作る
+fn window_plugin(window: &WindowConfig) -> WindowPlugin {
+    let monitor = window.monitor
+        .map(MonitorSelection::Index)
+        .unwrap_or(MonitorSelection::Primary);
+
+    let mode = match window.mode {
+        WindowModeSetting::Windowed => WindowMode::Windowed,
+        WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen(monitor),
+        WindowModeSetting::Fullscreen => WindowMode::Fullscreen(monitor, VideoModeSelection::Current),
+    };
+    let position = match window.position {
+        Some([x, y]) => WindowPosition::At(IVec2::new(x, y)),
+        None => WindowPosition::Centered(monitor),
+    };
+
+    WindowPlugin {
+        primary_window: Some(Window {
+            mode,
+            position,
+            resolution: (window.width, window.height).into(),
+            present_mode: if window.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync },
+            window_level: if window.always_on_top { WindowLevel::AlwaysOnTop } else { WindowLevel::Normal },
+            ..default()
+        }),
+        primary_cursor_options: Some(CursorOptions {
+            visible: !window.hide_cursor,
+            ..default()
+        }),
+        ..default()
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct Fonts {
-    text_font: TextFont
+    text_font: TextFont,
+    language_fonts: HashMap<Language, Handle<Font>>,
+    fallbacks: Vec<Handle<Font>>,
+    font_data: HashMap<AssetId<Font>, Arc<GlyphCoverage>>,
 }
-    
+
+impl Fonts {
+    // config.toml の [fonts] のフォントを読み込み始める（掲示板とプレビューで共通）
+    pub fn load(config: &Config, asset_server: &AssetServer) -> Self {
+        let text_font = TextFont {
+            font: asset_server.load(config.fonts.primary.clone()),
+            font_size: config.text_size,
+            // LEDパネルではアンチエイリアスで文字が滲むのでドット単位で描画する
+            font_smoothing: if config.led.is_some() { FontSmoothing::None } else { FontSmoothing::default() },
+            ..default()
+        };
+        Self {
+            text_font,
+            language_fonts: config.fonts.languages.iter()
+                .map(|(language, path)| (*language, asset_server.load(path.clone())))
+                .collect(),
+            fallbacks: config.fonts.fallbacks.iter()
+                .map(|path| asset_server.load(path.clone()))
+                .collect(),
+            font_data: HashMap::new(),
+        }
+    }
+
+    // 言語ごとのフォントが設定されていればそれを使う
+    pub fn font_for(&self, language: Language) -> TextFont {
+        let mut font = self.text_font.clone();
+        if let Some(handle) = self.language_fonts.get(&language) {
+            font.font = handle.clone();
+        }
+        font
+    }
+
+    // 設定されたすべてのフォント
+    pub fn handles(&self) -> Vec<Handle<Font>> {
+        let mut handles = vec![self.text_font.font.clone()];
+        handles.extend(self.language_fonts.values().cloned());
+        handles.extend(self.fallbacks.iter().cloned());
+        handles
+    }
+
+    // first → primary → fallbacks の順に文字を探すフォントの並び
+    fn chain(&self, first: &Handle<Font>) -> Vec<(Handle<Font>, FontData)> {
+        let mut chain = vec![first.clone()];
+        if *first != self.text_font.font {
+            chain.push(self.text_font.font.clone());
+        }
+        chain.extend(self.fallbacks.iter().cloned());
+        chain.into_iter()
+            .map(|handle| {
+                let data = self.font_data.get(&handle.id()).cloned();
+                (handle, data)
+            })
+            .collect()
+    }
+
+    // 表示中の断片を、いま読み込まれているフォントで分け直す
+    fn resplit(&self, segment: &TextSegment) -> Vec<TextSegment> {
+        text_spawner::split_by_font(&segment.text, &segment.font, &self.chain(&segment.font.font))
+    }
+
+    pub fn plain(&self, text: &str) -> Vec<TextSegment> {
+        text_spawner::split_by_font(text, &self.text_font, &self.chain(&self.text_font.font))
+    }
+
+    // 指定した言語のテキストを区切り付きで並べる
+    pub fn segments(&self, source: &TextSource, languages: &[Language]) -> Vec<TextSegment> {
+        let mut segments = Vec::new();
+        for (i, language) in languages.iter().enumerate() {
+            if i > 0 {
+                segments.push(TextSegment::new("  /  ", self.text_font.clone()));
+            }
+            let text = source.translation(*language).unwrap_or_default();
+            let font = self.font_for(*language);
+            segments.extend(text_spawner::split_by_font(text, &font, &self.chain(&font.font)));
+        }
+        segments
+    }
+}
+
+// 読み込みが終わったフォントの文字の一覧を Fonts に控えておく（フォールバック判定用）
+fn collect_font_data(mut fonts: ResMut<Fonts>, font_assets: Res<Assets<Font>>) {
+    for handle in fonts.handles() {
+        if fonts.font_data.contains_key(&handle.id()) {
+            continue;
+        }
+        if let Some(font) = font_assets.get(&handle) {
+            let coverage = GlyphCoverage::from_font_data(&font.data);
+            fonts.font_data.insert(handle.id(), Arc::new(coverage));
+        }
+    }
+}
+
+// フォントの読み込みより先に表示されたテキストは、フォントがそろった時点でフォールバックを付け直す。
+// 入りの効果の途中（タイプライターで打っている途中など）のテキストは、効果が終わってから付け直す
+#[allow(clippy::type_complexity)]
+fn resplit_showing_texts(
+    mut cmds: Commands,
+    fonts: Res<Fonts>,
+    mut loaded: Local<usize>,
+    mut writer: Text2dWriter,
+    mut entered: RemovedComponents<transition::TransitionIn>,
+    query: Query<Entity, (With<Text2d>, With<Showing>, Without<transition::TransitionIn>)>,
+) {
+    let entered: Vec<Entity> = entered.read().filter(|entity| query.contains(*entity)).collect();
+    let targets = if fonts.font_data.len() > *loaded {
+        *loaded = fonts.font_data.len();
+        query.iter().collect()
+    } else {
+        entered
+    };
+
+    for entity in targets {
+        let mut current = Vec::new();
+        writer.for_each(entity, |_, _, text, font, _| {
+            current.push(TextSegment::new(text.as_str(), font.clone()));
+        });
+        let segments = current.iter().flat_map(|segment| fonts.resplit(segment)).collect();
+        text_spawner::replace_segments(&mut cmds, &mut writer, entity, segments);
+    }
+}
+
 #[derive(Component)]
 struct TextScroll;
 
@@ -72,6 +337,53 @@ pub struct TextQueue {
     texts: Vec<TextSource>,
     current_index: usize,
     current_preset: String,
+    language_mode: LanguageMode,
+    pass: usize,          // プリセットを何周したか
+    language_step: usize, // Cycle モードで現在のテキストを何言語目まで表示したか
+}
+
+impl TextQueue {
+    pub fn languages(&self, index: usize) -> Vec<Language> {
+        language::select_languages(&self.texts[index], self.language_mode, self.pass, self.language_step)
+    }
+
+    // 表示し終えたテキストの次へ進む。Cycle モードでは全言語を表示してから進む
+    pub fn advance(&mut self) {
+        if self.language_mode == LanguageMode::Cycle {
+            self.language_step += 1;
+            if self.language_step < self.texts[self.current_index].languages().len() {
+                return;
+            }
+            self.language_step = 0;
+        }
+        self.current_index += 1;
+    }
+
+    // 次に表示するテキスト。末尾まで来ていれば次は先頭
+    pub fn next_content(&self) -> String {
+        self.texts.get(self.current_index)
+            .or(self.texts.first())
+            .map(|next| next.content.clone())
+            .unwrap_or_default()
+    }
+
+    // 末尾まで来たら先頭に戻って次の周回にする
+    pub fn wrap(&mut self) {
+        if self.current_index >= self.texts.len() {
+            self.current_index = 0;
+            self.language_step = 0;
+            self.pass += 1;
+        }
+    }
+
+    pub fn switch_preset(&mut self, name: &str, texts: Vec<TextSource>, language_mode: LanguageMode) {
+        self.texts = texts;
+        self.current_preset = name.to_string();
+        self.current_index = 0;
+        self.language_mode = language_mode;
+        self.pass = 0;
+        self.language_step = 0;
+    }
 }
 
 #[derive(Resource, Default)]
@@ -84,44 +396,129 @@ pub struct ScrollingSpeed {
     speed: f32,
 }
 
+// カメラに映っている範囲（ワールド座標）
+#[derive(Resource, Default)]
+pub struct Viewport {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Viewport {
+    pub fn width(&self) -> f32 {
+        self.right - self.left
+    }
+}
+
+#[derive(Component)]
+pub struct BoardCamera;
+
 #[derive(Component)]
 pub struct Showing;
 
 #[derive(Component)]
 pub struct LoopingText {
-    pub original_x: f32,
-    pub text_width: f32,
     pub loop_speed: f32,
 }
 
+// 掲示（プリセット）のテキスト
+#[derive(Component)]
+pub struct BulletinText;
+
+#[derive(Resource, Default)]
+pub struct Autoplay {
+    pub active: bool,
+    held: f32, // 今のテキストを表示している時間
+}
+
+// レイアウト確定待ちのスクロールテキスト
+#[derive(Component)]
+pub struct PendingScroll {
+    pub duration: f32,
+}
+
 fn setup(
     mut cmds: Commands,
     config: Res<Config>,
     asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
     mut fonts: ResMut<Fonts>,
-    mut bingo: ResMut<BingoState>
 ) {
-    let font = asset_server.load("fonts/ipag.ttf");
-    let text_font = TextFont {
-        font: font,
-      font_size: config.text_size,
-        ..default()
+    *fonts = Fonts::load(&config, &asset_server);
+
+    let target = match &config.led {
+        Some(led) => led::setup_led_output(&mut cmds, &mut images, led),
+        None => RenderTarget::default(),
     };
-    let bingo_state = BingoState::new();
-    fonts.text_font = text_font;
-    bingo.numbers = bingo_state.numbers;
-    bingo.index = bingo_state.index;
+    let msaa = if config.led.is_some() { Msaa::Off } else { Msaa::default() };
     cmds.spawn((
         Camera2d,
+        Camera {
+            target,
+            ..default()
+        },
+        msaa,
+        BoardCamera,
         Transform::from_translation(Vec3::new(config.camera_offset, 0.0, 0.0)),
     ));
 }
 
+// 実際のウィンドウサイズとカメラ位置から表示範囲を求める（リサイズや投影の設定にも追従）
+fn update_viewport(
+    mut viewport: ResMut<Viewport>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<BoardCamera>>,
+) {
+    let Ok((camera, transform)) = camera_query.single() else {
+        return;
+    };
+    let Some(size) = camera.logical_viewport_size() else {
+        return;
+    };
+    // 画面の左端と右端をワールド座標に直す
+    let middle = size.y / 2.0;
+    let (Ok(left), Ok(right)) = (
+        camera.viewport_to_world_2d(transform, Vec2::new(0.0, middle)),
+        camera.viewport_to_world_2d(transform, Vec2::new(size.x, middle)),
+    ) else {
+        return;
+    };
+
+    viewport.left = left.x;
+    viewport.right = right.x;
+}
+
+// レイアウトが確定したテキストを画面右外に配置し、スクロール速度を決める
+#[allow(clippy::type_complexity)]
+fn start_pending_scroll(
+    mut cmds: Commands,
+    viewport: Res<Viewport>,
+    mut scrolling_speed: ResMut<ScrollingSpeed>,
+    mut query: Query<(Entity, &mut Transform, &mut Visibility, &TextLayoutInfo, &PendingScroll, Has<LoopingText>)>,
+) {
+    if viewport.width() <= 0.0 {
+        return;
+    }
+
+    for (entity, mut transform, mut visibility, info, pending, looping) in &mut query {
+        if info.scale_factor == 0.0 {
+            continue;
+        }
+
+        let text_width = info.size.x;
+        transform.translation.x = text::scroll_start_x(text_width, &viewport);
+        if !looping {
+            scrolling_speed.speed = text::calc_speed(text_width, pending.duration, &viewport);
+        }
+        *visibility = Visibility::Inherited;
+        cmds.entity(entity).remove::<PendingScroll>();
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn text_scroll(
     time: Res<Time>,
     scrolling_state: Res<ScrollingState>,
     scrolling_speed: Res<ScrollingSpeed>,
-    mut query: Query<&mut Transform, (With<TextScroll>, Without<LoopingText>)>,
+    mut query: Query<&mut Transform, (With<TextScroll>, Without<LoopingText>, Without<PendingScroll>)>,
 ) {
     if !scrolling_state.is_active {
         return;
@@ -132,134 +529,185 @@ fn text_scroll(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn text_loop(
     time: Res<Time>,
-    config: Res<Config>,
-    mut query: Query<(&mut Transform, &LoopingText), With<Showing>>,
+    viewport: Res<Viewport>,
+    text_queue: Res<TextQueue>,
+    broadcaster: Option<Res<server::ResponseBroadcaster>>,
+    mut reader: Text2dReader,
+    mut query: Query<(Entity, &mut Transform, &LoopingText, &TextLayoutInfo), (With<Showing>, Without<PendingScroll>)>,
 ) {
-    for (mut transform, looping_text) in &mut query {
+    for (entity, mut transform, looping_text, info) in &mut query {
         // 左に移動
         transform.translation.x -= looping_text.loop_speed * time.delta_secs();
         
-        // 通常のスクロールと同じ判定ロジックを使用
-        let text_left_edge = transform.translation.x + (looping_text.text_width + config.window_width) / 2.0 + 5.0;
-        
         // テキストが完全に画面左端を通り過ぎたかチェック（テキスト全体が画面外に出るまで待つ）
-        if text_left_edge < 0.0 {
+        if text::has_scrolled_out(transform.translation.x, info.size.x, &viewport) {
             // 右端から再開
-            transform.translation.x = looping_text.original_x;
+            transform.translation.x = text::scroll_start_x(info.size.x, &viewport);
+            broadcast_text_status(&broadcaster, "looped", &mut reader, entity, &text_queue);
         }
     }
 }
      
-fn handle_keyboard_action(
-    keys: Res<ButtonInput<KeyCode>>,
-    mut scrolling_state: ResMut<ScrollingState>,
-    mut text_queue: ResMut<TextQueue>,
-    mut cmds: Commands,
-    mut bingo: ResMut<BingoState>,
-    mut countdown_timer: ResMut<CountdownTimer>,
+// 自動再生。スクロールが終わるか、静止・ループのテキストを hold 秒表示したら次へ進む。
+// ビンゴやカウントダウンなど掲示以外を表示している間は待つ
+#[allow(clippy::type_complexity)]
+fn autoplay_system(
+    time: Res<Time>,
     config: Res<Config>,
-    fonts: Res<Fonts>,
-    text_query: Query<Entity, With<Showing>>,
-    mut scrolling_speed: ResMut<ScrollingSpeed>,
+    countdown_timer: Res<CountdownTimer>,
+    mut autoplay: ResMut<Autoplay>,
+    mut show_text: MessageWriter<action::ShowText>,
+    query: Query<(Has<BulletinText>, Has<TextScroll>, Has<PendingScroll>), With<Showing>>,
 ) {
-    if keys.just_pressed(KeyCode::Enter) {
-            for entity in text_query.iter() {
-                cmds.entity(entity).despawn();
-	    }
-
-	// カウントダウンを停止
-	countdown_timer.stop();
-
-	if text_queue.texts[text_queue.current_index].duration == 0.0 {
-	    text_spawner::spawn_static_text(&mut cmds, &text_queue.texts[text_queue.current_index].content.clone(), fonts.text_font.clone());
-	} else {
-	    text_spawner::spawn_text(
-		&mut cmds,
-		&text_queue.texts[text_queue.current_index].content.clone(),
-		&text_queue.texts[text_queue.current_index].duration,
-		fonts.text_font.clone(),
-		&config,
-		&mut *scrolling_speed,
-        );
-	}
-
-        text_queue.current_index += 1;
-
-	scrolling_state.is_active = true;
-    };
-    if keys.just_pressed(KeyCode::KeyB) {
-	for entity in text_query.iter() {
-	    cmds.entity(entity).despawn();
-	}
-	
-	// カウントダウンを停止
-	countdown_timer.stop();
-	
-	text_spawner::spawn_static_text(&mut cmds, &bingo.next().unwrap_or(0).to_string(), fonts.text_font.clone());
-    }
-    if keys.just_pressed(KeyCode::KeyC) {
-        for entity in text_query.iter() {
-            cmds.entity(entity).despawn();
-        }
-        
-        // 通常のカウントダウン開始（10秒）
-        countdown_timer.mode = countdown::CountdownMode::Normal;
-        countdown_timer.initial_seconds = 10.0;
-        countdown_timer.timer = bevy::time::Timer::from_seconds(10.0, bevy::time::TimerMode::Once);
-        countdown_timer.start();
-    }
-    if keys.just_pressed(KeyCode::KeyX) {
-        for entity in text_query.iter() {
-            cmds.entity(entity).despawn();
+    if !autoplay.active || countdown_timer.is_active {
+        autoplay.held = 0.0;
+        return;
+    }
+
+    let mut waiting = false;
+    for (bulletin, scrolling, pending) in query.iter() {
+        if !bulletin || scrolling || pending {
+            waiting = true;
         }
-        
-        // 加速カウントダウン開始（15秒）
-        countdown_timer.mode = countdown::CountdownMode::Accelerated;
-        countdown_timer.initial_seconds = 15.0;
-        countdown_timer.timer = bevy::time::Timer::from_seconds(15.0, bevy::time::TimerMode::Once);
-        countdown_timer.start();
-    }
-    if keys.just_pressed(KeyCode::KeyZ) {
-        for entity in text_query.iter() {
-            cmds.entity(entity).despawn();
+    }
+    if waiting {
+        autoplay.held = 0.0;
+        return;
+    }
+    if !query.is_empty() {
+        autoplay.held += time.delta_secs();
+        if autoplay.held < config.autoplay.hold {
+            return;
         }
-        
-        // 減速カウントダウン開始（15秒）
-        countdown_timer.mode = countdown::CountdownMode::Decelerated;
-        countdown_timer.initial_seconds = 15.0;
-        countdown_timer.timer = bevy::time::Timer::from_seconds(15.0, bevy::time::TimerMode::Once);
-        countdown_timer.start();
-    }	
+    }
+
+    autoplay.held = 0.0;
+    show_text.write(action::ShowText::default());
+}
+
+// 押されたキーを [keybindings] の割り当てで操作にして送る
+fn handle_keyboard_action(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<keybindings::KeyBindings>,
+    mut actions: action::InputActions,
+) {
+    for action in bindings.triggered(|combo| combo.just_pressed(&keys)) {
+        actions.send(action);
+    }
+}
+
+// 表示中のテキストの状態を WebSocket に知らせる
+fn broadcast_text_status(
+    broadcaster: &Option<Res<server::ResponseBroadcaster>>,
+    status: &str,
+    reader: &mut Text2dReader,
+    entity: Entity,
+    text_queue: &TextQueue,
+) {
+    let Some(broadcaster) = broadcaster else {
+        return;
+    };
+    let text: String = reader.iter(entity).map(|(_, _, text, _, _)| text).collect();
+    let _ = broadcaster.sender.send(server::WsResponse::Text(server::TextResponse {
+        status: status.to_string(),
+        text,
+        next_text: text_queue.next_content(),
+    }));
 }
 
+#[allow(clippy::type_complexity)]
 fn check_text_completion(
     mut cmds: Commands,
-    config: Res<Config>,
-    text_queue: ResMut<TextQueue>,
+    viewport: Res<Viewport>,
+    text_queue: Res<TextQueue>,
     mut scrolling_state: ResMut<ScrollingState>,
-    query: Query<(Entity, &Transform, &TextLayoutInfo), (With<TextScroll>, Without<LoopingText>)>,
+    broadcaster: Option<Res<server::ResponseBroadcaster>>,
+    mut reader: Text2dReader,
+    query: Query<(Entity, &Transform, &TextLayoutInfo), (With<TextScroll>, Without<LoopingText>, Without<PendingScroll>)>,
 ) {
 
     for (entity, transform, info) in query.iter() {
-        let text_width = info.size.x;
-        let text_left_edge =
-            transform.translation.x + (text_width + config.window_width) / 2.0 + 5.0;
-
         // テキストが完全に画面左端を通り過ぎたかチェック（テキスト全体が画面外に出るまで待つ）
-        if text_left_edge < 0.0 {
+        if text::has_scrolled_out(transform.translation.x, info.size.x, &viewport) {
+            broadcast_text_status(&broadcaster, "scrolled_out", &mut reader, entity, &text_queue);
+
             // 現在のテキストエンティティを削除
             cmds.entity(entity).despawn();
 
             // スクロールを停止
             scrolling_state.is_active = false;
 	    
-            println!(
-                "Next: {} ",
-                text_queue.texts[text_queue.current_index].content
-            );
+            if let Some(next) = text_queue.texts.get(text_queue.current_index) {
+                println!("Next: {} ", next.content);
+            }
             break; // 一度に一つのテキストのみ処理
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(language_mode: LanguageMode) -> TextQueue {
+        let texts = vec![
+            TextSource {
+                content: "ようこそ".to_string(),
+                en: Some("Welcome".to_string()),
+                ko: Some("환영합니다".to_string()),
+                ..default()
+            },
+            TextSource {
+                content: "開演まで".to_string(),
+                ..default()
+            },
+        ];
+        TextQueue {
+            texts,
+            current_index: 0,
+            current_preset: "default".to_string(),
+            language_mode,
+            pass: 0,
+            language_step: 0,
+        }
+    }
+
+    #[test]
+    fn cycle_shows_every_language_before_advancing() {
+        let mut queue = queue(LanguageMode::Cycle);
+        let mut shown = Vec::new();
+        for _ in 0..5 {
+            queue.wrap();
+            shown.push((queue.current_index, queue.languages(queue.current_index)));
+            queue.advance();
+        }
+        assert_eq!(shown, [
+            (0, vec![Language::Ja]),
+            (0, vec![Language::En]),
+            (0, vec![Language::Ko]),
+            (1, vec![Language::Ja]),
+            (0, vec![Language::Ja]),
+        ]);
+        assert_eq!(queue.pass, 1);
+    }
+
+    #[test]
+    fn wrap_starts_next_pass() {
+        let mut queue = queue(LanguageMode::PerPass);
+        queue.advance();
+        queue.wrap();
+        assert_eq!((queue.current_index, queue.pass), (1, 0));
+
+        queue.advance();
+        assert_eq!(queue.next_content(), "ようこそ");
+        queue.wrap();
+        assert_eq!((queue.current_index, queue.pass), (0, 1));
+        assert_eq!(queue.languages(0), [Language::En]);
+
+        queue.switch_preset("other", Vec::new(), LanguageMode::Primary);
+        assert_eq!((queue.current_index, queue.pass, queue.next_content().as_str()), (0, 0, ""));
+    }
+}
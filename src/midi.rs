@@ -0,0 +1,282 @@
+use crate::{
+    action::InputAction,
+    keybindings::Bindings,
+    loader::{Config, EbbPaths, LoadDiagnostics, LoadError},
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt};
+#[cfg(feature = "midi")]
+use {
+    crate::action::InputActions,
+    midir::{Ignore, MidiInputConnection},
+    std::{process::ExitCode, sync::mpsc},
+};
+
+// [midi] テーブル。port を省略すると最初に見つかった入力ポートにつなぐ。
+// notes はノートオン、controls はコントロールチェンジ（値が 64 以上になったとき）で操作する
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MidiConfig {
+    pub port: Option<String>, // ポート名の一部
+    pub virtual_port: bool,   // 他のソフトからつなげる仮想ポートを作る（Linux・macOS）
+    pub notes: toml::Table,   // 操作ごとのノート番号。MidiBindings::load で1項目ずつ読む
+    pub controls: toml::Table,
+}
+
+impl MidiConfig {
+    // config.toml の [midi] を読む。port などが読めなければ読み込みエラーにして MIDI を使わない
+    pub fn from_table(paths: &EbbPaths, raw: &toml::Table, diagnostics: &mut LoadDiagnostics) -> Option<Self> {
+        match toml::Value::Table(raw.clone()).try_into() {
+            Ok(config) => Some(config),
+            Err(e) => {
+                diagnostics.push(LoadError::new(&paths.config_file(), None, None, format!("[midi] {}", e.message())));
+                None
+            }
+        }
+    }
+}
+
+// config.toml の [midi] と、その割り当て。[midi] がなければ None
+pub fn load(paths: &EbbPaths, config: &Config, diagnostics: &mut LoadDiagnostics) -> Option<(MidiConfig, MidiBindings)> {
+    let midi = MidiConfig::from_table(paths, config.midi.as_ref()?, diagnostics)?;
+    let bindings = MidiBindings::load(paths, &midi, diagnostics);
+    Some((midi, bindings))
+}
+
+// ノート番号・コントロール番号（0～127）
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "i64")]
+pub struct MidiNumber(pub u8);
+
+impl TryFrom<i64> for MidiNumber {
+    type Error = String;
+
+    fn try_from(number: i64) -> Result<Self, Self::Error> {
+        match u8::try_from(number) {
+            Ok(number) if number <= 127 => Ok(MidiNumber(number)),
+            _ => Err(format!("{} is out of range (0-127)", number)),
+        }
+    }
+}
+
+impl fmt::Display for MidiNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// ノート番号・コントロール番号の割り当て
+#[derive(Resource)]
+pub struct MidiBindings {
+    notes: Bindings<MidiNumber>,
+    controls: Bindings<MidiNumber>,
+}
+
+impl MidiBindings {
+    // MIDI は既定の割り当てがないので、[midi] で指定した操作だけ反応する
+    pub fn load(paths: &EbbPaths, config: &MidiConfig, diagnostics: &mut LoadDiagnostics) -> Self {
+        let notes = Bindings::parse("midi.notes", &config.notes, |_| Vec::new(), paths, diagnostics);
+        let controls = Bindings::parse("midi.controls", &config.controls, |_| Vec::new(), paths, diagnostics);
+        Self { notes, controls }
+    }
+
+    // ヘルプに出す割り当て（"note 36"、"cc 41"）
+    pub fn describe(&self, action: InputAction) -> Vec<String> {
+        let notes = self.notes.get(action).iter().map(|note| format!("note {}", note));
+        let controls = self.controls.get(action).iter().map(|control| format!("cc {}", control));
+        notes.chain(controls).collect()
+    }
+}
+
+// コントロールチェンジはボタンを押して値が 64 以上になったときだけ反応する
+const CONTROL_ON: u8 = 64;
+
+// 受け取った MIDI メッセージを操作にする。コントロールチェンジは直前の値と比べるので覚えておく
+#[derive(Default)]
+#[cfg_attr(not(feature = "midi"), allow(dead_code))]
+pub struct MidiDecoder {
+    levels: HashMap<u8, u8>,
+}
+
+#[cfg_attr(not(feature = "midi"), allow(dead_code))]
+impl MidiDecoder {
+    // チャンネルは区別しない
+    pub fn decode(&mut self, message: &[u8], bindings: &MidiBindings) -> Vec<InputAction> {
+        let &[status, number, value, ..] = message else {
+            return Vec::new();
+        };
+        match status & 0xF0 {
+            // ベロシティ 0 のノートオンはノートオフ
+            0x90 if value > 0 => bindings.notes.triggered(|note| note.0 == number).collect(),
+            0xB0 => {
+                let previous = self.levels.insert(number, value).unwrap_or(0);
+                if previous < CONTROL_ON && value >= CONTROL_ON {
+                    bindings.controls.triggered(|control| control.0 == number).collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "midi")]
+const CLIENT_NAME: &str = "NexBoard";
+
+// MIDI の入力。コールバックは別スレッドで呼ばれるので、チャンネルでメインスレッドに渡す
+#[cfg(feature = "midi")]
+pub struct MidiInput {
+    _connection: MidiInputConnection<()>,
+    receiver: mpsc::Receiver<Vec<u8>>,
+    decoder: MidiDecoder,
+}
+
+// [midi] の設定でポートにつなぐ。つなげなければ読み込みエラーとして報告して MIDI なしで動かす
+#[cfg(feature = "midi")]
+pub fn connect(paths: &EbbPaths, config: &MidiConfig, diagnostics: &mut LoadDiagnostics) -> Option<MidiInput> {
+    let mut report = |reason: String| {
+        diagnostics.push(LoadError::new(&paths.config_file(), None, None, format!("[midi] {}", reason)));
+    };
+
+    let mut input = match midir::MidiInput::new(CLIENT_NAME) {
+        Ok(input) => input,
+        Err(e) => {
+            report(e.to_string());
+            return None;
+        }
+    };
+    input.ignore(Ignore::All);
+
+    let (sender, receiver) = mpsc::channel();
+    let callback = move |_: u64, message: &[u8], _: &mut ()| {
+        let _ = sender.send(message.to_vec());
+    };
+
+    let connection = if config.virtual_port {
+        create_virtual(input, callback)
+    } else {
+        let port = input.ports().into_iter().find(|port| {
+            let name = input.port_name(port).unwrap_or_default();
+            config.port.as_ref().is_none_or(|wanted| name.contains(wanted.as_str()))
+        });
+        let Some(port) = port else {
+            report(match &config.port {
+                Some(wanted) => format!("no MIDI input port matching `{}`", wanted),
+                None => "no MIDI input port found".to_string(),
+            });
+            return None;
+        };
+        let name = input.port_name(&port).unwrap_or_default();
+        println!("MIDI input: {}", name);
+        input.connect(&port, CLIENT_NAME, callback, ()).map_err(|e| e.to_string())
+    };
+
+    match connection {
+        Ok(connection) => Some(MidiInput {
+            _connection: connection,
+            receiver,
+            decoder: MidiDecoder::default(),
+        }),
+        Err(e) => {
+            report(e);
+            None
+        }
+    }
+}
+
+// 他のソフトや aconnect でつなげる仮想ポートを作る
+#[cfg(all(feature = "midi", unix))]
+fn create_virtual(
+    input: midir::MidiInput,
+    callback: impl FnMut(u64, &[u8], &mut ()) + Send + 'static,
+) -> Result<MidiInputConnection<()>, String> {
+    use midir::os::unix::VirtualInput;
+    println!("MIDI input: virtual port {}", CLIENT_NAME);
+    input.create_virtual(CLIENT_NAME, callback, ()).map_err(|e| e.to_string())
+}
+
+#[cfg(all(feature = "midi", not(unix)))]
+fn create_virtual(
+    _: midir::MidiInput,
+    _: impl FnMut(u64, &[u8], &mut ()) + Send + 'static,
+) -> Result<MidiInputConnection<()>, String> {
+    Err("virtual ports are not supported on this platform".to_string())
+}
+
+#[cfg(feature = "midi")]
+pub fn midi_input_system(mut midi: NonSendMut<MidiInput>, bindings: Res<MidiBindings>, mut actions: InputActions) {
+    let MidiInput { receiver, decoder, .. } = &mut *midi;
+    for message in receiver.try_iter() {
+        for action in decoder.decode(&message, &bindings) {
+            actions.send(action);
+        }
+    }
+}
+
+// つなげる MIDI 入力ポートの一覧（[midi] port に書く名前の確認用）
+#[cfg(feature = "midi")]
+pub fn list_ports() -> ExitCode {
+    let input = match midir::MidiInput::new(CLIENT_NAME) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Err: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let ports = input.ports();
+    if ports.is_empty() {
+        println!("No MIDI input ports found");
+    }
+    for port in &ports {
+        println!("{}", input.port_name(port).unwrap_or_default());
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(toml: &str) -> (MidiBindings, LoadDiagnostics) {
+        let paths = EbbPaths::new(Some("/nonexistent".into()), None);
+        let config: MidiConfig = toml::from_str(toml).unwrap();
+        let mut diagnostics = LoadDiagnostics::default();
+        (MidiBindings::load(&paths, &config, &mut diagnostics), diagnostics)
+    }
+
+    #[test]
+    fn note_on_triggers_on_any_channel() {
+        let (bindings, _) = bindings("[notes]\nnext_text = 36\nbingo_next = [37, 38]\n");
+        let mut decoder = MidiDecoder::default();
+        assert_eq!(decoder.decode(&[0x90, 36, 100], &bindings), vec![InputAction::NextText]);
+        assert_eq!(decoder.decode(&[0x93, 38, 1], &bindings), vec![InputAction::BingoNext]);
+        // ベロシティ 0 のノートオンとノートオフは反応しない
+        assert!(decoder.decode(&[0x90, 36, 0], &bindings).is_empty());
+        assert!(decoder.decode(&[0x80, 36, 64], &bindings).is_empty());
+        assert!(decoder.decode(&[0x90, 40, 100], &bindings).is_empty());
+        assert!(decoder.decode(&[0x90, 36], &bindings).is_empty());
+    }
+
+    #[test]
+    fn control_change_triggers_when_crossing_64() {
+        let (bindings, _) = bindings("[controls]\ncountdown_normal = 41\n");
+        let mut decoder = MidiDecoder::default();
+        assert_eq!(decoder.decode(&[0xB0, 41, 127], &bindings), vec![InputAction::CountdownNormal]);
+        // 押したままの間は反応しない
+        assert!(decoder.decode(&[0xB0, 41, 100], &bindings).is_empty());
+        assert!(decoder.decode(&[0xB0, 41, 0], &bindings).is_empty());
+        assert_eq!(decoder.decode(&[0xB1, 41, 64], &bindings), vec![InputAction::CountdownNormal]);
+        assert!(decoder.decode(&[0xB0, 42, 127], &bindings).is_empty());
+    }
+
+    #[test]
+    fn out_of_range_number_only_drops_that_entry() {
+        let (bindings, diagnostics) = bindings("[notes]\nnext_text = 300\nbingo_next = 128\ntoggle_help = 36\n");
+        assert_eq!(diagnostics.errors.len(), 2);
+        assert!(diagnostics.errors.iter().all(|e| e.reason.contains("out of range (0-127)")));
+        assert_eq!(bindings.describe(InputAction::ToggleHelp), vec!["note 36"]);
+        assert!(bindings.describe(InputAction::NextText).is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    language::{self, Language, LanguageMode},
+    loader::{self, Config, EbbPaths, LoadDiagnostics, TextSource, TransitionConfig},
+    text, text_spawner, BoardCamera, Fonts, Viewport,
+};
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    camera::RenderTarget,
+    color::palettes::tailwind::SLATE_900,
+    prelude::*,
+    render::{
+        render_resource::TextureFormat,
+        view::screenshot::{save_to_disk, Screenshot, ScreenshotCaptured},
+    },
+    text::TextLayoutInfo,
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+// ウィンドウを開かずにプリセットの各テキストをPNGに書き出す。
+// 静止テキストは1枚、スクロールテキストは frames 枚に分けて保存する。
+// 掲示板と同じフォールバックと言語ごとのフォントで描き、言語を切り替えるプリセットは言語ごとに書き出す
+pub fn run(paths: &EbbPaths, preset: &str, out_dir: PathBuf, frames: u32) -> ExitCode {
+    let mut diagnostics = LoadDiagnostics::default();
+    let conf: Config = loader::unwrap_conf(paths, &mut diagnostics);
+    let presets = loader::load_all_presets(paths, &mut diagnostics);
+    let Some(texts) = presets.presets.get(preset) else {
+        println!("Err: Preset '{}' not found", preset);
+        return ExitCode::FAILURE;
+    };
+    let language_mode = presets.meta(preset).language_mode;
+    let items = texts.iter().enumerate()
+        .flat_map(|(index, source)| preview_items(index, source, language_mode))
+        .collect();
+    if let Err(e) = std::fs::create_dir_all(&out_dir) {
+        println!("Err: Can't create output directory {:?}: {}", out_dir, e);
+        return ExitCode::FAILURE;
+    }
+
+    let exit = App::new()
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                })
+                .disable::<WinitPlugin>(),
+        )
+        .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / 60.0)))
+        .insert_resource(ClearColor(Color::Srgba(SLATE_900)))
+        .insert_resource(PreviewJob {
+            preset: preset.to_string(),
+            items,
+            out_dir,
+            frames: frames.max(1),
+            index: 0,
+            step: PreviewStep::Spawn,
+            requested: 0,
+            saved: 0,
+        })
+        .insert_resource(conf)
+        .init_resource::<Viewport>()
+        .init_resource::<Fonts>()
+        .add_systems(Startup, setup_preview)
+        .add_systems(Update, (crate::update_viewport, crate::collect_font_data))
+        .add_systems(Update, preview_system.after(crate::update_viewport).after(crate::collect_font_data))
+        .run();
+
+    match exit {
+        AppExit::Success => ExitCode::SUCCESS,
+        AppExit::Error(_) => ExitCode::FAILURE,
+    }
+}
+
+// 書き出す1つのテキスト。language は言語ごとに書き出すときのファイル名の末尾
+struct PreviewItem {
+    index: usize,
+    source: TextSource,
+    languages: Vec<Language>,
+    language: Option<Language>,
+}
+
+// 掲示板が表示する言語の組み合わせ。Cycle と PerPass はいずれ全言語を1つずつ表示するので言語ごとに分ける
+fn preview_items(index: usize, source: &TextSource, mode: LanguageMode) -> Vec<PreviewItem> {
+    let item = |languages: Vec<Language>, language| PreviewItem {
+        index,
+        source: source.clone(),
+        languages,
+        language,
+    };
+    match mode {
+        LanguageMode::Cycle | LanguageMode::PerPass if source.languages().len() > 1 => {
+            source.languages().into_iter().map(|language| item(vec![language], Some(language))).collect()
+        }
+        _ => vec![item(language::select_languages(source, mode, 0, 0), None)],
+    }
+}
+
+#[derive(Resource)]
+struct PreviewJob {
+    preset: String,
+    items: Vec<PreviewItem>,
+    out_dir: PathBuf,
+    frames: u32,
+    index: usize,
+    step: PreviewStep,
+    requested: u32,
+    saved: u32,
+}
+
+enum PreviewStep {
+    Spawn,
+    Layout,
+    Capture { positions: Vec<f32>, frame: usize },
+    Finish,
+}
+
+#[derive(Resource)]
+struct PreviewTarget {
+    image: Handle<Image>,
+}
+
+#[derive(Component)]
+struct PreviewText;
+
+fn setup_preview(
+    mut cmds: Commands,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut fonts: ResMut<Fonts>,
+) {
+    *fonts = Fonts::load(&config, &asset_server);
+    let (width, height) = match &config.led {
+        Some(led) => (led.width, led.height),
+        None => (config.window.width, config.window.height),
+    };
+    let image = images.add(Image::new_target_texture(width, height, TextureFormat::Bgra8UnormSrgb));
+
+    cmds.spawn((
+        Camera2d,
+        Camera {
+            target: RenderTarget::Image(image.clone().into()),
+            ..default()
+        },
+        BoardCamera,
+        Transform::from_translation(Vec3::new(config.camera_offset, 0.0, 0.0)),
+    ));
+
+    cmds.insert_resource(PreviewTarget { image });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn preview_system(
+    mut cmds: Commands,
+    mut job: ResMut<PreviewJob>,
+    target: Res<PreviewTarget>,
+    fonts: Res<Fonts>,
+    asset_server: Res<AssetServer>,
+    viewport: Res<Viewport>,
+    mut exit: MessageWriter<AppExit>,
+    mut query: Query<(Entity, &mut Transform, &TextLayoutInfo), With<PreviewText>>,
+) {
+    let job = &mut *job;
+    match &mut job.step {
+        PreviewStep::Spawn => {
+            if asset_server.load_state(&fonts.text_font.font).is_failed() {
+                println!("Err: Can't load font for preview");
+                exit.write(AppExit::error());
+                return;
+            }
+            // 読み込めなかったフォールバックは掲示板と同じく飛ばす
+            let settled = fonts.handles().iter()
+                .all(|handle| asset_server.is_loaded_with_dependencies(handle) || asset_server.load_state(handle).is_failed());
+            if !settled || viewport.width() <= 0.0 {
+                return;
+            }
+            for (entity, _, _) in query.iter() {
+                cmds.entity(entity).despawn();
+            }
+            let Some(item) = job.items.get(job.index) else {
+                job.step = PreviewStep::Finish;
+                return;
+            };
+            let segments = fonts.segments(&item.source, &item.languages);
+            text_spawner::spawn_static_text(&mut cmds, segments, &TransitionConfig::default())
+                .insert(PreviewText);
+            job.step = PreviewStep::Layout;
+        }
+        PreviewStep::Layout => {
+            let Ok((_, _, info)) = query.single() else {
+                return;
+            };
+            if info.scale_factor == 0.0 {
+                return;
+            }
+            // 静止テキストは中央、スクロールテキストは画面右外から左外までを等分する
+            let positions = if job.items[job.index].source.duration == 0.0 {
+                vec![0.0]
+            } else {
+                let start = text::scroll_start_x(info.size.x, &viewport);
+                let distance = info.size.x + viewport.width();
+                (0..job.frames)
+                    .map(|frame| start - distance * (frame + 1) as f32 / (job.frames + 1) as f32)
+                    .collect()
+            };
+            job.step = PreviewStep::Capture { positions, frame: 0 };
+        }
+        PreviewStep::Capture { positions, frame } => {
+            let Ok((_, mut transform, _)) = query.single_mut() else {
+                return;
+            };
+            transform.translation.x = positions[*frame];
+
+            let item = &job.items[job.index];
+            let mut file_name = format!("{}_{:03}", job.preset, item.index);
+            if let Some(language) = item.language {
+                file_name = format!("{}_{}", file_name, language.code());
+            }
+            if positions.len() > 1 {
+                file_name = format!("{}_{:03}", file_name, frame);
+            }
+            let file_name = format!("{}.png", file_name);
+            cmds.spawn(Screenshot::image(target.image.clone()))
+                .observe(save_to_disk(job.out_dir.join(file_name)))
+                .observe(|_: On<ScreenshotCaptured>, mut job: ResMut<PreviewJob>| {
+                    job.saved += 1;
+                });
+            job.requested += 1;
+
+            *frame += 1;
+            if *frame >= positions.len() {
+                job.index += 1;
+                job.step = PreviewStep::Spawn;
+            }
+        }
+        PreviewStep::Finish => {
+            // 非同期で保存される画像が揃うまで待つ
+            if job.saved >= job.requested {
+                println!("Rendered {} images to {:?}", job.saved, job.out_dir);
+                exit.write(AppExit::Success);
+            }
+        }
+    }
+}
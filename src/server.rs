@@ -7,40 +7,89 @@ use axum::{
 };
 use bevy_tokio_tasks::TokioTasksRuntime;
 use bevy::prelude::*;
-use tokio::sync::{mpsc, broadcast};
+use crate::action;
+use tokio::sync::{mpsc, broadcast, watch};
 use serde::{Deserialize, Serialize};
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(tag = "mode")]
 pub enum WsCommand {
     #[serde(rename = "bulletin")]
-    Bulletin { preset: String, index: u32 },
+    Bulletin { preset: String, index: u32, transition: Option<crate::transition::TransitionEffect> },
     #[serde(rename = "bingo")]
-    Bingo { method: BingoMethod },
+    Bingo {
+        method: BingoMethod,
+        // new_game のときだけ使う。省略した項目は config.toml の [bingo] のまま
+        variant: Option<crate::bingo::BingoVariant>,
+        min: Option<u32>,
+        max: Option<u32>,
+        exclude: Option<Vec<u32>>,
+        #[serde(default, deserialize_with = "crate::bingo::deserialize_seed")]
+        seed: Option<u64>, // 数値または10進の文字列
+    },
     #[serde(rename = "countdown")]
-    Countdown { method: CountdownMethod, seconds: Option<f32>, countdown_mode: Option<String> },
+    Countdown {
+        method: CountdownMethod,
+        seconds: Option<f32>,
+        countdown_mode: Option<String>,
+        // start のときだけ使う。省略時は config.toml の [countdown]
+        format: Option<crate::countdown::CountdownFormat>,
+        label: Option<String>,
+        until: Option<String>, // "15:00" のような終了時刻。指定すると seconds と countdown_mode は使わない
+        on_finish: Option<crate::countdown::FinishAction>,
+    },
+    #[serde(rename = "stopwatch")]
+    Stopwatch { method: StopwatchMethod, label: Option<String> },
+    #[serde(rename = "autoplay")]
+    Autoplay { enabled: Option<bool> }, // 省略で切り替え
+    #[serde(rename = "sound")]
+    Sound { muted: Option<bool>, volume: Option<f32> }, // 省略した項目はそのまま
     #[serde(rename = "list_presets")]
     ListPresets,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum StopwatchMethod {
+    Start,
+    Stop,
+    Lap,
+    Reset,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum BingoMethod {
     Next,
+    NewGame,
+    ExportLog,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum CountdownMethod {
     Start,
+    Pause,
+    Resume,
+    Cancel,
+    AddSeconds, // seconds だけ残り時間を増やす（マイナスで減らす）
 }
 
+// どのレスポンスかは "type" で見分ける（"bulletin"、"bingo_game" など）
 #[derive(Debug, Deserialize, Serialize, Clone)]
-#[serde(untagged)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum WsResponse {
     Bulletin(BulletinResponse),
     Bingo(BingoResponse),
+    BingoExhausted(BingoExhaustedResponse),
+    BingoError(BingoErrorResponse),
+    BingoGame(BingoGameResponse),
+    BingoLog(BingoLogResponse),
+    Stopwatch(StopwatchResponse),
+    Autoplay(AutoplayResponse),
+    Sound(SoundResponse),
+    Text(TextResponse),
     Countdown(CountdownResponse),
     PresetList(PresetListResponse),
 }
@@ -50,12 +99,38 @@ pub struct BulletinResponse {
     pub prev_text: String,
     pub now_text: String,
     pub next_text: String,
+    pub language: String, // 表示中の言語（"ja"、複数なら "ja+en"）
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BingoResponse {
-    pub current: u8,
-    pub no: u8,
+    pub current: u32,
+    pub no: usize,
+    pub label: String, // 画面に出した文字列（"B 7"、抽選なら名前）
+}
+
+// 引ける番号がもうない
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BingoExhaustedResponse {
+    pub drawn: usize, // このゲームで引いた数
+}
+
+// new_game を受け付けなかった（今のゲームはそのまま）
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BingoErrorResponse {
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BingoGameResponse {
+    pub variant: String,
+    pub count: usize,       // 引ける番号の数
+    pub commitment: String, // 引く順のハッシュ（seed は抽選ログを書き出すまで返さない）
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BingoLogResponse {
+    pub path: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -63,9 +138,46 @@ pub struct CountdownResponse {
     pub status: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StopwatchResponse {
+    pub status: String, // "started" / "stopped" / "lap" / "reset"
+    pub elapsed: f32,   // 開始からの秒数
+    pub laps: Vec<f32>, // 各ラップの開始からの秒数
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AutoplayResponse {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TextResponse {
+    pub status: String, // "scrolled_out"（スクロールし終えた）/ "looped"（ループの先頭に戻った）/ "shown"（カウントダウン後のメッセージを出した）
+    pub text: String,
+    pub next_text: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SoundResponse {
+    pub muted: bool,
+    pub volume: f32,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PresetListResponse {
     pub presets: Vec<String>,
+    pub details: Vec<PresetInfo>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PresetInfo {
+    pub name: String,
+    pub title: String,
+    pub order: Option<i32>,
+    pub default: bool,
+    pub tags: Vec<String>,
+    pub description: String,
+    pub count: usize,
 }
 
 #[derive(Resource)]
@@ -82,11 +194,22 @@ pub struct CommandSender {
 #[derive(Resource)]
 pub struct ResponseBroadcaster {
     pub sender: broadcast::Sender<WsResponse>,
+    bingo_game: watch::Sender<Option<WsResponse>>, // 今のビンゴのゲーム。あとからつないだクライアントにも最初に送る
+}
+
+impl ResponseBroadcaster {
+    // ビンゴのゲームの開始を知らせ、つないでくるクライアントのために控えておく
+    pub fn announce_bingo_game(&self, game: BingoGameResponse) {
+        let response = WsResponse::BingoGame(game);
+        self.bingo_game.send_replace(Some(response.clone()));
+        let _ = self.sender.send(response);
+    }
 }
 
 pub fn setup_websocket_server(app: &mut App) {
     let (command_tx, command_rx) = mpsc::channel::<WsCommand>(100);
     let (response_tx, _response_rx) = broadcast::channel::<WsResponse>(100);
+    let (bingo_game_tx, _bingo_game_rx) = watch::channel::<Option<WsResponse>>(None);
     
     app.insert_resource(CommandSender {
         sender: command_tx,
@@ -99,10 +222,11 @@ pub fn setup_websocket_server(app: &mut App) {
     
     app.insert_resource(ResponseBroadcaster {
         sender: response_tx,
+        bingo_game: bingo_game_tx,
     });
     
     app.add_systems(Startup, start_axum_server);
-    app.add_systems(Update, handle_websocket_commands);
+    app.add_systems(Update, handle_websocket_commands.before(action::ActionSet));
 }
 
 fn start_axum_server(
@@ -112,12 +236,14 @@ fn start_axum_server(
 ) {
     let command_tx = command_sender.sender.clone();
     let response_tx = response_broadcaster.sender.clone();
+    let bingo_game_rx = response_broadcaster.bingo_game.subscribe();
     
     runtime.spawn_background_task(move |_ctx| async move {
         let app = Router::new()
             .route("/ws", get(ws_handler))
             .layer(Extension(command_tx))
-            .layer(Extension(response_tx));
+            .layer(Extension(response_tx))
+            .layer(Extension(bingo_game_rx));
             
         let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
             .await
@@ -135,30 +261,32 @@ async fn ws_handler(
     ws: WebSocketUpgrade,
     Extension(command_tx): Extension<mpsc::Sender<WsCommand>>,
     Extension(response_tx): Extension<broadcast::Sender<WsResponse>>,
+    Extension(bingo_game_rx): Extension<watch::Receiver<Option<WsResponse>>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_websocket(socket, command_tx, response_tx))
+    ws.on_upgrade(move |socket| handle_websocket(socket, command_tx, response_tx, bingo_game_rx))
 }
 
 async fn handle_websocket(
     socket: WebSocket,
     command_tx: mpsc::Sender<WsCommand>,
     response_tx: broadcast::Sender<WsResponse>,
+    bingo_game_rx: watch::Receiver<Option<WsResponse>>,
 ) {
     let (mut ws_sender, mut ws_receiver) = socket.split();
     let mut response_rx = response_tx.subscribe();
+    // つないだ時点のビンゴの commitment を最初に送る
+    let bingo_game = bingo_game_rx.borrow().clone();
     
     // レスポンス送信タスク
     let response_task = tokio::spawn(async move {
+        if let Some(response) = bingo_game
+            && send_response(&mut ws_sender, &response).await.is_err()
+        {
+            return;
+        }
         while let Ok(response) = response_rx.recv().await {
-            match serde_json::to_string(&response) {
-                Ok(json) => {
-                    if ws_sender.send(Message::Text(json.into())).await.is_err() {
-                        break;
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Failed to serialize response: {}", e);
-                }
+            if send_response(&mut ws_sender, &response).await.is_err() {
+                break;
             }
         }
     });
@@ -200,145 +328,107 @@ async fn handle_websocket(
     }
 }
 
+// レスポンスを JSON にして送る。送れなければ接続が切れている
+async fn send_response(
+    ws_sender: &mut SplitSink<WebSocket, Message>,
+    response: &WsResponse,
+) -> Result<(), axum::Error> {
+    match serde_json::to_string(response) {
+        Ok(json) => ws_sender.send(Message::Text(json.into())).await,
+        Err(e) => {
+            eprintln!("Failed to serialize response: {}", e);
+            Ok(())
+        }
+    }
+}
+
+// 受け取ったコマンドを操作のメッセージにして送る（処理と通知は action のシステムで行う）
 fn handle_websocket_commands(
-    mut commands: Commands,
     mut ws_channel: ResMut<WebSocketChannel>,
-    mut text_queue: ResMut<crate::TextQueue>,
     preset_manager: Res<crate::loader::PresetManager>,
-    mut bingo_state: ResMut<crate::bingo::BingoState>,
-    mut countdown_timer: ResMut<crate::countdown::CountdownTimer>,
-    mut scrolling_state: ResMut<crate::ScrollingState>,
-    mut scrolling_speed: ResMut<crate::ScrollingSpeed>,
-    config: Res<crate::loader::Config>,
-    fonts: Res<crate::Fonts>,
-    text_query: Query<Entity, With<crate::Showing>>,
+    mut actions: action::ActionWriters,
 ) {
     while let Ok(command) = ws_channel.command_receiver.try_recv() {
         match command {
-            WsCommand::Bulletin { preset, index } => {
-                // プリセットが指定されていて、現在のプリセットと異なる場合は切り替え
-                if text_queue.current_preset != preset {
-                    if let Some(new_texts) = preset_manager.presets.get(&preset) {
-                        text_queue.texts = new_texts.clone();
-                        text_queue.current_preset = preset.clone();
-                        text_queue.current_index = 0;
-                        println!("Switched to preset: {}", preset);
-                    } else {
-                        println!("Preset '{}' not found, using current preset '{}'", preset, text_queue.current_preset);
-                    }
-                }
-                
-                // 現在のテキストを削除
-                for entity in text_query.iter() {
-                    commands.entity(entity).despawn();
-                }
-                
-                // 新しいテキストをスポーン
-                if let Some(text_source) = text_queue.texts.get(index as usize) {
-                    let text_content = text_source.content.clone();
-                    let text_duration = text_source.duration;
-
-		    if text_duration == 0.0 {
-			crate::text_spawner::spawn_static_text(&mut commands, &text_content, fonts.text_font.clone());
-		    } else {
-                    crate::text_spawner::spawn_text(
-                        &mut commands,
-                        &text_content,
-                        &text_duration,
-                        fonts.text_font.clone(),
-                        &config,
-                        &mut *scrolling_speed,
-                    );
-		    }
-                    
-                    text_queue.current_index = index as usize;
-                    scrolling_state.is_active = true;
-                    
-                    // レスポンスを送信
-                    let prev_text = text_queue.texts.get(index.saturating_sub(1) as usize)
-                        .map(|t| t.content.clone())
-                        .unwrap_or_default();
-                    let now_text = text_content;
-                    let next_text = text_queue.texts.get((index + 1) as usize)
-                        .map(|t| t.content.clone())
-                        .unwrap_or_default();
-                    
-                    let response = WsResponse::Bulletin(BulletinResponse {
-                        prev_text,
-                        now_text,
-                        next_text,
-                    });
-                    
-                    let _ = ws_channel.response_sender.send(response);
-                } else {
-                    println!("Text index {} not found in preset '{}'", index, text_queue.current_preset);
-                }
+            WsCommand::Bulletin { preset, index, transition } => {
+                actions.show_text.write(action::ShowText {
+                    preset: Some(preset),
+                    index: Some(index as usize),
+                    transition,
+                });
             }
-            WsCommand::Bingo { method } => {
+            WsCommand::Bingo { method, variant, min, max, exclude, seed } => {
                 match method {
                     BingoMethod::Next => {
-                        // 現在のテキストを削除
-                        for entity in text_query.iter() {
-                            commands.entity(entity).despawn();
-                        }
-                        
-                        if let Some(number) = bingo_state.next() {
-                            crate::text_spawner::spawn_static_text(
-                                &mut commands,
-                                &number.to_string(),
-                                fonts.text_font.clone(),
-                            );
-                            
-                            let response = WsResponse::Bingo(BingoResponse {
-                                current: number,
-                                no: bingo_state.index as u8,
-                            });
-                            
-                            let _ = ws_channel.response_sender.send(response);
-                        }
+                        actions.bingo_next.write(action::BingoNext);
+                    }
+                    BingoMethod::NewGame => {
+                        actions.new_bingo_game.write(action::NewBingoGame { variant, min, max, exclude, seed });
+                    }
+                    BingoMethod::ExportLog => {
+                        actions.export_bingo_log.write(action::ExportBingoLog);
                     }
                 }
             }
-            WsCommand::Countdown { method, seconds, countdown_mode } => {
-                match method {
+            WsCommand::Countdown { method, seconds, countdown_mode, format, label, until, on_finish } => {
+                let control = match method {
                     CountdownMethod::Start => {
-                        // 他のテキストを削除
-                        for entity in text_query.iter() {
-                            commands.entity(entity).despawn();
-                        }
-                        
-                        // カウントダウンモードを設定（デフォルト: Decelerated）
-                        let mode_value = match countdown_mode.as_deref() {
+                        // カウントダウンモード（デフォルト: Decelerated）
+                        let mode = match countdown_mode.as_deref() {
                             Some("normal") => crate::countdown::CountdownMode::Normal,
                             Some("accelerated") => crate::countdown::CountdownMode::Accelerated,
-                            Some("decelerated") | None => crate::countdown::CountdownMode::Decelerated, // デフォルト
                             _ => crate::countdown::CountdownMode::Decelerated,
                         };
-                        
-                        let duration = seconds.unwrap_or(15.0); // デフォルト15秒
-                        
-                        countdown_timer.mode = mode_value;
-                        countdown_timer.initial_seconds = duration;
-                        countdown_timer.timer = bevy::time::Timer::from_seconds(duration, bevy::time::TimerMode::Once);
-                        countdown_timer.start();
-                        
-                        let response = WsResponse::Countdown(CountdownResponse {
-                            status: format!("started {} mode ({}s)", 
-                                match mode_value {
-                                    crate::countdown::CountdownMode::Normal => "normal",
-                                    crate::countdown::CountdownMode::Accelerated => "accelerated", 
-                                    crate::countdown::CountdownMode::Decelerated => "decelerated",
-                                }, duration),
+                        actions.start_countdown.write(action::StartCountdown {
+                            seconds: seconds.unwrap_or(15.0), // デフォルト15秒
+                            mode,
+                            format,
+                            label,
+                            until,
+                            on_finish,
                         });
-                        
-                        let _ = ws_channel.response_sender.send(response);
+                        continue;
                     }
-                }
+                    CountdownMethod::Pause => action::CountdownControl::Pause,
+                    CountdownMethod::Resume => action::CountdownControl::Resume,
+                    CountdownMethod::Cancel => action::CountdownControl::Cancel,
+                    CountdownMethod::AddSeconds => action::CountdownControl::AddSeconds(seconds),
+                };
+                actions.countdown_control.write(control);
+            }
+            WsCommand::Stopwatch { method, label } => {
+                let control = match method {
+                    StopwatchMethod::Start => action::StopwatchControl::Start { label },
+                    StopwatchMethod::Stop => action::StopwatchControl::Stop,
+                    StopwatchMethod::Lap => action::StopwatchControl::Lap,
+                    StopwatchMethod::Reset => action::StopwatchControl::Reset,
+                };
+                actions.stopwatch_control.write(control);
+            }
+            WsCommand::Autoplay { enabled } => {
+                actions.set_autoplay.write(action::SetAutoplay { enabled });
+            }
+            WsCommand::Sound { muted, volume } => {
+                actions.set_sound.write(action::SetSound { muted, volume });
             }
             WsCommand::ListPresets => {
-                let preset_names: Vec<String> = preset_manager.presets.keys().cloned().collect();
+                // メタデータの並び順で返す
+                let preset_names = preset_manager.sorted_names();
+                let details = preset_names.iter().map(|name| {
+                    let meta = preset_manager.meta(name);
+                    PresetInfo {
+                        name: name.clone(),
+                        title: meta.title.unwrap_or_else(|| name.clone()),
+                        order: meta.order,
+                        default: meta.default,
+                        tags: meta.tags,
+                        description: meta.description.unwrap_or_default(),
+                        count: preset_manager.presets[name].len(),
+                    }
+                }).collect();
                 let response = WsResponse::PresetList(PresetListResponse {
                     presets: preset_names,
+                    details,
                 });
                 let _ = ws_channel.response_sender.send(response);
             }
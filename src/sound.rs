@@ -0,0 +1,145 @@
+use crate::loader::{Config, EbbPaths, LoadDiagnostics, LoadError, SoundConfig};
+use bevy::{
+    audio::{AudioSink, AudioSinkPlayback, Volume},
+    prelude::*,
+};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+// 鳴らす場面
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundCue {
+    Tick,     // カウントダウンの数字が変わったとき
+    Buzzer,   // カウントダウンが 0 になったとき
+    Drumroll, // ビンゴのルーレットが回り始めたとき
+    Reveal,   // ビンゴの番号が決まったとき
+    Chime,    // chime を付けたテキストを出すとき
+}
+
+impl SoundCue {
+    pub const ALL: [SoundCue; 5] = [
+        SoundCue::Tick,
+        SoundCue::Buzzer,
+        SoundCue::Drumroll,
+        SoundCue::Reveal,
+        SoundCue::Chime,
+    ];
+
+    fn file(&self, config: &SoundConfig) -> Option<PathBuf> {
+        match self {
+            SoundCue::Tick => config.tick.clone(),
+            SoundCue::Buzzer => config.buzzer.clone(),
+            SoundCue::Drumroll => config.drumroll.clone(),
+            SoundCue::Reveal => config.reveal.clone(),
+            SoundCue::Chime => config.chime.clone(),
+        }
+    }
+}
+
+// 効果音。ファイルを指定していない音は鳴らさない
+#[derive(Resource, Default)]
+pub struct Sounds {
+    handles: HashMap<SoundCue, Handle<AudioSource>>,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+// 再生中の効果音。音声出力がないと再生が始まらないので、しばらくしたら片付ける
+#[derive(Component)]
+pub struct SoundPlayer {
+    timer: Timer,
+}
+
+// 再生が始まらなかった効果音を片付けるまでの時間（秒）
+const START_TIMEOUT: f32 = 2.0;
+
+impl Sounds {
+    // 鳴らした音のエンティティを返す（途中で止めたいときに使う）
+    pub fn play(&self, cmds: &mut Commands, cue: SoundCue) -> Option<Entity> {
+        if self.muted {
+            return None;
+        }
+        let handle = self.handles.get(&cue)?;
+        let entity = cmds.spawn((
+            AudioPlayer::new(handle.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::Linear(self.volume)),
+            SoundPlayer {
+                timer: Timer::from_seconds(START_TIMEOUT, TimerMode::Once),
+            },
+        ));
+        Some(entity.id())
+    }
+
+    // 0.0～1.0 に収める。数でない値（NaN や無限大）は受け付けない
+    pub fn set_volume(&mut self, volume: f32) -> bool {
+        if !volume.is_finite() {
+            return false;
+        }
+        self.volume = volume.clamp(0.0, 1.0);
+        true
+    }
+}
+
+// [sound] のファイルを config ディレクトリ から読み込む。読めないファイルと数でない音量は読み込みエラーとして報告する
+pub fn read_sound_files(paths: &EbbPaths, config: &SoundConfig, diagnostics: &mut LoadDiagnostics) -> Vec<(SoundCue, Vec<u8>)> {
+    if !config.volume.is_finite() {
+        diagnostics.push(LoadError::new(&paths.config_file(), None, None, "`sound.volume` must be a number from 0.0 to 1.0"));
+    }
+    let mut files = Vec::new();
+    for cue in SoundCue::ALL {
+        let Some(file) = cue.file(config) else {
+            continue;
+        };
+        let path = paths.config_dir.join(file);
+        match std::fs::read(&path) {
+            Ok(bytes) => files.push((cue, bytes)),
+            Err(e) => diagnostics.push(LoadError::io(&path, e)),
+        }
+    }
+    files
+}
+
+pub fn setup_sounds(
+    mut sounds: ResMut<Sounds>,
+    mut sources: ResMut<Assets<AudioSource>>,
+    mut diagnostics: ResMut<LoadDiagnostics>,
+    paths: Res<EbbPaths>,
+    config: Res<Config>,
+) {
+    // 数でない音量は read_sound_files が報告するので、ここでは既定の音量にしておく
+    if !sounds.set_volume(config.sound.volume) {
+        sounds.volume = 1.0;
+    }
+    sounds.muted = config.sound.muted;
+    for (cue, bytes) in read_sound_files(&paths, &config.sound, &mut diagnostics) {
+        let handle = sources.add(AudioSource { bytes: Arc::from(bytes) });
+        sounds.handles.insert(cue, handle);
+    }
+}
+
+// 音量・ミュートの変更を再生中の音にも反映し、始まらなかった音を片付ける
+pub fn sound_system(
+    mut cmds: Commands,
+    time: Res<Time>,
+    sounds: Res<Sounds>,
+    mut players: Query<(Entity, &mut SoundPlayer, Option<&mut AudioSink>)>,
+) {
+    for (entity, mut player, sink) in &mut players {
+        match sink {
+            Some(mut sink) => {
+                if sounds.is_changed() {
+                    sink.set_volume(Volume::Linear(sounds.volume));
+                    if sounds.muted {
+                        sink.mute();
+                    } else {
+                        sink.unmute();
+                    }
+                }
+            }
+            None => {
+                if player.timer.tick(time.delta()).just_finished() {
+                    cmds.entity(entity).despawn();
+                }
+            }
+        }
+    }
+}
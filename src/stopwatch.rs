@@ -0,0 +1,145 @@
+use crate::{
+    countdown::CountdownFormat,
+    loader::{Config, TransitionConfig},
+    server::{ResponseBroadcaster, StopwatchResponse, WsResponse},
+    text_spawner,
+    transition::FadeComponent,
+    Fonts, Showing,
+};
+use bevy::prelude::*;
+use std::time::{Duration, Instant};
+
+// 経過時間を数えるストップウォッチ。表示はカウントダウンと同じ見た目で 0.1 秒ごとに書き換える
+#[derive(Resource, Default)]
+pub struct Stopwatch {
+    accumulated: Duration,       // 止めるまでに数えた時間
+    started_at: Option<Instant>, // 動いている間は開始した時刻
+    laps: Vec<f32>,
+    pub shown: bool, // 他の表示に切り替わったら false になる
+    pub format: CountdownFormat,
+    pub label: Option<String>,
+    last_text: String,
+    entity: Option<Entity>,
+    pending: Vec<StopwatchResponse>, // stopwatch_system で WebSocket に送る
+}
+
+#[derive(Component)]
+pub struct StopwatchText;
+
+impl Stopwatch {
+    pub fn elapsed(&self) -> f32 {
+        let running = self.started_at.map(|started| started.elapsed()).unwrap_or_default();
+        (self.accumulated + running).as_secs_f32()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn start(&mut self) {
+        if self.started_at.is_none() {
+            self.started_at = Some(Instant::now());
+        }
+        self.shown = true;
+        self.notify("started");
+    }
+
+    pub fn stop(&mut self) {
+        let Some(started) = self.started_at.take() else {
+            return;
+        };
+        self.accumulated += started.elapsed();
+        self.notify("stopped");
+    }
+
+    // 前のラップからの時間ではなく、開始からの時間を記録する
+    pub fn lap(&mut self) {
+        if !self.is_running() {
+            return;
+        }
+        self.laps.push(self.elapsed());
+        println!("Lap {}: {}", self.laps.len(), self.format.format_tenths(self.elapsed()));
+        self.notify("lap");
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulated = Duration::ZERO;
+        self.started_at = self.started_at.map(|_| Instant::now());
+        self.laps.clear();
+        self.notify("reset");
+    }
+
+    // 動いていれば止め、止まっていれば動かす
+    pub fn toggle(&mut self) {
+        if self.is_running() {
+            self.stop();
+        } else {
+            self.start();
+        }
+    }
+
+    fn notify(&mut self, status: &str) {
+        self.pending.push(StopwatchResponse {
+            status: status.to_string(),
+            elapsed: self.elapsed(),
+            laps: self.laps.clone(),
+        });
+    }
+
+    fn display_text(&self) -> String {
+        let time = self.format.format_tenths(self.elapsed());
+        match &self.label {
+            Some(label) => format!("{} {}", label, time),
+            None => time,
+        }
+    }
+}
+
+pub fn stopwatch_system(
+    mut cmds: Commands,
+    mut stopwatch: ResMut<Stopwatch>,
+    fonts: Res<Fonts>,
+    broadcaster: Option<Res<ResponseBroadcaster>>,
+    text_query: Query<Entity, (With<StopwatchText>, With<Showing>)>,
+    mut writer: Text2dWriter,
+) {
+    for response in std::mem::take(&mut stopwatch.pending) {
+        if let Some(broadcaster) = &broadcaster {
+            let _ = broadcaster.sender.send(WsResponse::Stopwatch(response));
+        }
+    }
+
+    // 別のテキストに切り替えられていたら表示をやめる（計測は続ける）
+    if let Some(entity) = stopwatch.entity
+        && !text_query.contains(entity)
+    {
+        stopwatch.shown = false;
+    }
+    if !stopwatch.shown {
+        stopwatch.entity = None;
+        stopwatch.last_text.clear();
+        return;
+    }
+
+    let text = stopwatch.display_text();
+    if text == stopwatch.last_text {
+        return;
+    }
+    match stopwatch.entity {
+        // 表示中なら文字だけ書き換える
+        Some(entity) => text_spawner::replace_segments(&mut cmds, &mut writer, entity, fonts.plain(&text)),
+        None => {
+            // 出始めはカウントダウンと同じようにフェードインする
+            let mut entity = text_spawner::spawn_static_text(&mut cmds, fonts.plain(&text), &TransitionConfig::default());
+            entity.insert((StopwatchText, FadeComponent::fade_in(0.3)));
+            stopwatch.entity = Some(entity.id());
+        }
+    }
+    stopwatch.last_text = text;
+}
+
+// config.toml の [stopwatch] から表示形式を設定する
+pub fn setup_stopwatch(mut stopwatch: ResMut<Stopwatch>, config: Res<Config>) {
+    stopwatch.format = config.stopwatch.format;
+    stopwatch.label = config.stopwatch.label.clone();
+}
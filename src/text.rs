@@ -1,20 +1,16 @@
-fn count_ascii(s: &str) -> (usize, usize) {
-    s.chars().fold((0, 0), |mut acc, c| {
-        if c.is_ascii() {
-            acc.0 += 1;
-        } else {
-            acc.1 += 1;
-        }
-        acc
-    })
+use crate::Viewport;
+
+// スクロール開始位置：テキストの左端が画面右端にちょうど接する位置
+pub fn scroll_start_x(text_width: f32, viewport: &Viewport) -> f32 {
+    viewport.right + text_width / 2.0
 }
 
-pub fn calc_text_offset(s: &str, tsize: f32, wwidth: f32) -> f32 {
-    let (ascii, non_ascii) = count_ascii(s);
-    println!("{}", non_ascii * 2 + ascii);
-    return ((non_ascii as f32 * tsize) + (ascii as f32 * tsize * 0.5) + wwidth) / 2.0 + 5.0;
+// テキストの右端が画面左端を完全に通り過ぎたか
+pub fn has_scrolled_out(x: f32, text_width: f32, viewport: &Viewport) -> bool {
+    x + text_width / 2.0 < viewport.left
 }
 
-pub fn calc_speed(w: f32, d: &f32, wwidth: f32) -> f32 {
-    return (w + wwidth) / d;
+// 画面右外から左外まで duration 秒で移動する速度
+pub fn calc_speed(text_width: f32, duration: f32, viewport: &Viewport) -> f32 {
+    (text_width + viewport.width()) / duration
 }
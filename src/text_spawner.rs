@@ -1,72 +1,190 @@
-use crate::{loader::Config, ScrollingSpeed, Showing, TextScroll, LoopingText};
+use crate::{loader::TransitionConfig, transition, LoopingText, PendingScroll, Showing, TextScroll};
 use bevy::{
     camera::visibility::NoFrustumCulling,
     color::palettes::tailwind::{SLATE_900, YELLOW_300},
     prelude::*,
 };
+use std::{collections::HashSet, sync::Arc};
 
-pub fn spawn_text(
-    cmds: &mut Commands,
-    text: &str,
-    duration: &f32,
-    text_font: TextFont,
-    config: &Config,
-    scrolling_speed: &mut ScrollingSpeed,
-) {
-    let text_offset = crate::text::calc_text_offset(text, config.text_size, config.window_width);
-    println!("Offset: {}, Duration: {}", text_offset, duration);
-    
-    // duration が 1000 以上の場合はループ再生
-    if *duration >= 1000.0 {
-        let text_width = text_offset * 2.0; // 概算のテキスト幅
-        let loop_speed = 500.0; // 500px/s
-        
-        cmds.spawn((
-            Text2d::new(text),
-            text_font,
-            TextColor(Color::Srgba(YELLOW_300)),
-            TextBackgroundColor(Color::Srgba(SLATE_900)),
-            Transform::from_translation(Vec3::new(config.window_width / 2.0 + text_width / 2.0 + 50.0, 0.0, 0.0)),
-            TextLayout::default(),
-            LoopingText {
-                original_x: text_offset,
-                text_width,
-                loop_speed,
-            },
-            Showing,
-        ))
-        .insert(NoFrustumCulling);
-    } else {
-        // 通常の1回だけのスクロール
-        cmds.spawn((
-            Text2d::new(text),
-            text_font,
-            TextColor(Color::Srgba(YELLOW_300)),
-            TextBackgroundColor(Color::Srgba(SLATE_900)),
-            Transform::from_translation(Vec3::new(text_offset, 0.0, 0.0)),
-            TextLayout::default(),
-            TextScroll,
-            Showing,
-        ))
-        .insert(NoFrustumCulling);
-        scrolling_speed.speed =
-            crate::text::calc_speed(text_offset * 2.0, duration, config.window_width);
+// フォントを指定したテキストの断片。複数あれば TextSpan として続けて表示する
+pub struct TextSegment {
+    pub text: String,
+    pub font: TextFont,
+}
+
+impl TextSegment {
+    pub fn new(text: impl Into<String>, font: TextFont) -> Self {
+        Self { text: text.into(), font }
+    }
+}
+
+// フォントが持っている文字の一覧。フォントを読み込んだときに一度だけ cmap から作る
+#[derive(Default)]
+pub struct GlyphCoverage {
+    chars: HashSet<char>,
+}
+
+impl GlyphCoverage {
+    pub fn from_font_data(data: &[u8]) -> Self {
+        let mut chars = HashSet::new();
+        let cmap = ttf_parser::Face::parse(data, 0).ok().and_then(|face| face.tables().cmap);
+        if let Some(cmap) = cmap {
+            for subtable in cmap.subtables {
+                if !subtable.is_unicode() {
+                    continue;
+                }
+                subtable.codepoints(|code| {
+                    if subtable.glyph_index(code).is_some_and(|glyph| glyph.0 != 0) {
+                        chars.extend(char::from_u32(code));
+                    }
+                });
+            }
+        }
+        Self { chars }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.chars.contains(&c)
+    }
+}
+
+// 読み込み済みフォントの文字の一覧（まだ読み込まれていなければ None）
+pub type FontData = Option<Arc<GlyphCoverage>>;
+
+fn has_glyph(data: &FontData, c: char) -> bool {
+    data.as_ref().is_some_and(|coverage| coverage.contains(c))
+}
+
+// 1文字ずつ、chain の先頭から順にその文字を持つフォントを選んで断片に分ける。
+// どのフォントにもない文字と空白は直前のフォントのままにする
+pub fn split_by_font(text: &str, base: &TextFont, chain: &[(Handle<Font>, FontData)]) -> Vec<TextSegment> {
+    let mut segments: Vec<TextSegment> = Vec::new();
+    for c in text.chars() {
+        let current = segments.last().map(|segment| segment.font.font.clone());
+        let font = if c.is_whitespace() || c.is_control() {
+            current.clone()
+        } else {
+            chain.iter()
+                .find(|(_, data)| has_glyph(data, c))
+                .map(|(handle, _)| handle.clone())
+                .or_else(|| current.clone())
+        }
+        .unwrap_or_else(|| base.font.clone());
+
+        match segments.last_mut() {
+            Some(segment) if current.as_ref() == Some(&font) => segment.text.push(c),
+            _ => segments.push(TextSegment::new(
+                c.to_string(),
+                TextFont { font, ..base.clone() },
+            )),
+        }
+    }
+    if segments.is_empty() {
+        segments.push(TextSegment::new("", base.clone()));
     }
+    segments
 }
 
-pub fn spawn_static_text(
-    cmds: &mut Commands,
-    text: &str,
-    text_font: TextFont,
-) {
-    cmds.spawn((
-        Text2d::new(text),
-        text_font,
+fn span_bundle(segment: TextSegment, color: TextColor) -> impl Bundle {
+    (
+        TextSpan::new(segment.text),
+        segment.font,
+        color,
+        TextBackgroundColor(Color::Srgba(SLATE_900)),
+    )
+}
+
+fn spawn_segments<'a>(cmds: &'a mut Commands, segments: Vec<TextSegment>) -> EntityCommands<'a> {
+    let mut segments = segments.into_iter();
+    let first = segments.next().unwrap_or_else(|| TextSegment::new("", TextFont::default()));
+
+    let mut entity = cmds.spawn((
+        Text2d::new(first.text),
+        first.font,
         TextColor(Color::Srgba(YELLOW_300)),
         TextBackgroundColor(Color::Srgba(SLATE_900)),
         Transform::from_xyz(0.0, 0.0, 0.0),
         TextLayout::default(),
         Showing,
-    ))
-    .insert(NoFrustumCulling);
+    ));
+    entity.with_children(|parent| {
+        for segment in segments {
+            parent.spawn(span_bundle(segment, TextColor(Color::Srgba(YELLOW_300))));
+        }
+    });
+    entity
+}
+
+// テキスト幅はレイアウト後にしか分からないため、非表示でスポーンして
+// start_pending_scroll で画面右外に配置する
+pub fn spawn_text<'a>(
+    cmds: &'a mut Commands,
+    segments: Vec<TextSegment>,
+    duration: f32,
+    transition: &TransitionConfig,
+) -> EntityCommands<'a> {
+    println!("Duration: {}", duration);
+
+    let mut entity = spawn_segments(cmds, segments);
+    entity.insert((Visibility::Hidden, PendingScroll { duration }));
+    transition::enter(&mut entity, transition, true);
+
+    // duration が 1000 以上の場合はループ再生
+    if duration >= 1000.0 {
+        entity.insert(LoopingText {
+            loop_speed: 500.0, // 500px/s
+        });
+    } else {
+        // 通常の1回だけのスクロール
+        entity.insert(TextScroll);
+    }
+    entity.insert(NoFrustumCulling);
+    entity
+}
+
+pub fn spawn_static_text<'a>(
+    cmds: &'a mut Commands,
+    segments: Vec<TextSegment>,
+    transition: &TransitionConfig,
+) -> EntityCommands<'a> {
+    let mut entity = spawn_segments(cmds, segments);
+    entity.insert(NoFrustumCulling);
+    transition::enter(&mut entity, transition, false);
+    entity
+}
+
+// 表示中のテキストを断片ごとに置き換える。足りない TextSpan は根と同じ色で追加し、余ったものは消す。
+// 変わっていない断片には触らないので、毎フレーム呼んでもレイアウトはやり直さない
+pub fn replace_segments(cmds: &mut Commands, writer: &mut Text2dWriter, entity: Entity, segments: Vec<TextSegment>) {
+    let mut segments = segments.into_iter();
+    let mut root_color = None;
+    let mut extra = Vec::new();
+    writer.for_each(entity, |span, _, mut text, mut font, color| {
+        root_color.get_or_insert(*color);
+        match segments.next() {
+            Some(segment) => {
+                if *text != segment.text {
+                    *text = segment.text;
+                }
+                if *font != segment.font {
+                    *font = segment.font;
+                }
+            }
+            None if span == entity => text.clear(),
+            None => extra.push(span),
+        }
+    });
+    for span in extra {
+        cmds.entity(span).despawn();
+    }
+
+    let rest: Vec<TextSegment> = segments.collect();
+    if !rest.is_empty() {
+        let color = root_color.unwrap_or(TextColor(Color::Srgba(YELLOW_300)));
+        cmds.entity(entity).with_children(|parent| {
+            for segment in rest {
+                parent.spawn(span_bundle(segment, color));
+            }
+        });
+    }
 }
@@ -0,0 +1,349 @@
+use crate::{loader::TransitionConfig, LoopingText, PendingScroll, Showing, TextScroll};
+use bevy::{
+    prelude::*,
+    sprite::Anchor,
+    text::TextLayoutInfo,
+};
+use serde::{Deserialize, Serialize};
+
+// テキストを切り替えるときの効果
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionEffect {
+    #[default]
+    Cut,        // すぐに切り替える
+    Crossfade,  // 前のテキストを消しながら次のテキストを浮かび上がらせる
+    SlideUp,    // 下から入って上へ抜ける
+    SlideDown,  // 上から入って下へ抜ける
+    Wipe,       // 左から右へ塗り替える
+    Typewriter, // 1文字ずつ表示する（消えるときはすぐ消える）
+}
+
+impl TransitionEffect {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TransitionEffect::Cut => "cut",
+            TransitionEffect::Crossfade => "crossfade",
+            TransitionEffect::SlideUp => "slide_up",
+            TransitionEffect::SlideDown => "slide_down",
+            TransitionEffect::Wipe => "wipe",
+            TransitionEffect::Typewriter => "typewriter",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct FadeComponent {
+    pub fade_in_duration: f32,
+    pub fade_out_duration: f32,
+    pub display_duration: f32,
+    pub current_time: f32,
+    pub phase: FadePhase,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FadePhase {
+    FadeIn,
+    Display,
+    FadeOut,
+    Complete,
+}
+
+impl FadeComponent {
+    // フェードイン → 表示 → フェードアウトしたら削除
+    pub fn new(fade_in_duration: f32, display_duration: f32, fade_out_duration: f32) -> Self {
+        Self {
+            fade_in_duration,
+            fade_out_duration,
+            display_duration,
+            current_time: 0.0,
+            phase: FadePhase::FadeIn,
+        }
+    }
+
+    // フェードインしたらそのまま表示し続ける
+    pub fn fade_in(duration: f32) -> Self {
+        Self::new(duration, f32::INFINITY, 0.0)
+    }
+
+    // 今の状態からフェードアウトして削除
+    pub fn fade_out(duration: f32) -> Self {
+        Self {
+            phase: FadePhase::FadeOut,
+            ..Self::new(0.0, 0.0, duration)
+        }
+    }
+}
+
+// 入ってくるテキストの効果
+#[derive(Component)]
+pub struct TransitionIn {
+    effect: TransitionEffect,
+    timer: Timer,
+    typed: Vec<String>, // タイプライター用に元の文字列を控えておく
+}
+
+// 出ていくテキストの効果。終わったら削除する
+#[derive(Component)]
+pub struct TransitionOut {
+    effect: TransitionEffect,
+    timer: Timer,
+}
+
+// ワイプでテキストを隠す背景色の板（テキストの子）。色は毎フレーム今の背景色（ClearColor）に合わせる
+#[derive(Component)]
+pub struct WipeCover;
+
+// 新しく表示したテキストに入りの効果を付ける。
+// スクロールテキストは右から入ってくるので、フェードとスライドだけ使う
+pub fn enter(entity: &mut EntityCommands, transition: &TransitionConfig, scrolling: bool) {
+    let duration = transition.duration.max(f32::EPSILON);
+    match transition.effect {
+        TransitionEffect::Cut => {}
+        TransitionEffect::Crossfade => {
+            entity.insert(FadeComponent::fade_in(duration));
+        }
+        TransitionEffect::Wipe | TransitionEffect::Typewriter if scrolling => {}
+        effect => {
+            // 大きさが分かって最初の位置に置くまでは隠しておく
+            entity.insert((
+                TransitionIn {
+                    effect,
+                    timer: Timer::from_seconds(duration, TimerMode::Once),
+                    typed: Vec::new(),
+                },
+                Visibility::Hidden,
+            ));
+            if effect == TransitionEffect::Wipe {
+                entity.with_child(wipe_cover());
+            }
+        }
+    }
+}
+
+// 表示中のテキストを出ていく効果付きで片付ける。
+// Showing などを外すので、以降は次のテキストの邪魔をしない
+pub fn leave(cmds: &mut Commands, entity: Entity, transition: &TransitionConfig) {
+    let duration = transition.duration.max(f32::EPSILON);
+    let mut entity = cmds.entity(entity);
+    match transition.effect {
+        TransitionEffect::Cut | TransitionEffect::Typewriter => {
+            entity.despawn();
+            return;
+        }
+        TransitionEffect::Crossfade => {
+            entity.insert(FadeComponent::fade_out(duration));
+        }
+        effect => {
+            entity.insert(TransitionOut {
+                effect,
+                timer: Timer::from_seconds(duration, TimerMode::Once),
+            });
+            if effect == TransitionEffect::Wipe {
+                entity.with_child(wipe_cover());
+            }
+        }
+    }
+    entity.remove::<(Showing, TextScroll, LoopingText, PendingScroll, TransitionIn)>();
+}
+
+// 表示中のテキストをまとめて片付ける
+pub fn leave_all(cmds: &mut Commands, entities: impl IntoIterator<Item = Entity>, transition: &TransitionConfig) {
+    for entity in entities {
+        leave(cmds, entity, transition);
+    }
+}
+
+fn wipe_cover() -> impl Bundle {
+    (
+        Sprite::from_color(Color::NONE, Vec2::ZERO),
+        Transform::from_xyz(0.0, 0.0, 0.5),
+        WipeCover,
+    )
+}
+
+// 入りの効果を進める
+#[allow(clippy::type_complexity)]
+pub fn transition_in_system(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut query: Query<(
+        Entity,
+        &mut TransitionIn,
+        &mut Transform,
+        &mut Anchor,
+        &mut Visibility,
+        &TextLayoutInfo,
+        Has<PendingScroll>,
+        Option<&Children>,
+    )>,
+    mut covers: Query<(&mut Sprite, &mut Transform), (With<WipeCover>, Without<TransitionIn>)>,
+    mut writer: Text2dWriter,
+    clear_color: Res<ClearColor>,
+) {
+    for (entity, mut transition, mut transform, mut anchor, mut visibility, info, pending, children) in &mut query {
+        // レイアウトが確定するまでは大きさが分からない（スクロールテキストは配置されるまで待つ）
+        if info.scale_factor == 0.0 || pending {
+            continue;
+        }
+        transition.timer.tick(time.delta());
+        let t = transition.timer.fraction();
+        let size = info.size;
+
+        match transition.effect {
+            TransitionEffect::SlideUp => transform.translation.y = -size.y * (1.0 - t),
+            TransitionEffect::SlideDown => transform.translation.y = size.y * (1.0 - t),
+            TransitionEffect::Wipe => {
+                // 右側の隠れている部分を縮めていく
+                let width = size.x * (1.0 - t);
+                for child in children.into_iter().flatten() {
+                    if let Ok((mut sprite, mut cover)) = covers.get_mut(*child) {
+                        sprite.color = clear_color.0;
+                        sprite.custom_size = Some(Vec2::new(width, size.y));
+                        cover.translation.x = size.x / 2.0 - width / 2.0;
+                    }
+                }
+            }
+            TransitionEffect::Typewriter => {
+                // 文字数が変わると幅も変わるので、左端を固定して伸ばしていく。
+                // 打ち終わるまでフォールバックの付け直し（resplit_showing_texts）は待つので断片の数は変わらない
+                if transition.typed.is_empty() {
+                    writer.for_each_text(entity, |text| transition.typed.push(text.clone()));
+                    *anchor = Anchor::CENTER_LEFT;
+                    transform.translation.x -= size.x / 2.0;
+                }
+                let total: usize = transition.typed.iter().map(|text| text.chars().count()).sum();
+                let mut remaining = (total as f32 * t).ceil() as usize;
+                let mut index = 0;
+                writer.for_each_text(entity, |mut text| {
+                    if let Some(typed) = transition.typed.get(index) {
+                        let shown: String = typed.chars().take(remaining).collect();
+                        remaining -= shown.chars().count();
+                        if *text != shown {
+                            *text = shown;
+                        }
+                    }
+                    index += 1;
+                });
+            }
+            TransitionEffect::Cut | TransitionEffect::Crossfade => {}
+        }
+        *visibility = Visibility::Inherited;
+
+        if transition.timer.is_finished() {
+            if transition.effect == TransitionEffect::Wipe {
+                for child in children.into_iter().flatten() {
+                    if covers.contains(*child) {
+                        cmds.entity(*child).despawn();
+                    }
+                }
+            }
+            cmds.entity(entity).remove::<TransitionIn>();
+        }
+    }
+}
+
+// 出ていく効果を進め、終わったら削除する
+#[allow(clippy::type_complexity)]
+pub fn transition_out_system(
+    mut cmds: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut TransitionOut, &mut Transform, &TextLayoutInfo, Option<&Children>)>,
+    mut covers: Query<(&mut Sprite, &mut Transform), (With<WipeCover>, Without<TransitionOut>)>,
+    clear_color: Res<ClearColor>,
+) {
+    for (entity, mut transition, mut transform, info, children) in &mut query {
+        transition.timer.tick(time.delta());
+        let t = transition.timer.fraction();
+        let size = info.size;
+
+        match transition.effect {
+            TransitionEffect::SlideUp => transform.translation.y = size.y * t,
+            TransitionEffect::SlideDown => transform.translation.y = -size.y * t,
+            TransitionEffect::Wipe => {
+                // 左から隠していく
+                let width = size.x * t;
+                for child in children.into_iter().flatten() {
+                    if let Ok((mut sprite, mut cover)) = covers.get_mut(*child) {
+                        sprite.color = clear_color.0;
+                        sprite.custom_size = Some(Vec2::new(width, size.y));
+                        cover.translation.x = -size.x / 2.0 + width / 2.0;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if transition.timer.is_finished() {
+            cmds.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn fade_system(
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut FadeComponent, Option<&Children>)>,
+    mut backgrounds: Query<&mut TextBackgroundColor>,
+    mut writer: Text2dWriter,
+    mut commands: Commands,
+) {
+    let mut entities_to_remove = Vec::new();
+
+    for (entity, mut fade, children) in query.iter_mut() {
+        fade.current_time += time.delta_secs();
+
+        let alpha = match fade.phase {
+            FadePhase::FadeIn => {
+                if fade.current_time >= fade.fade_in_duration {
+                    fade.phase = FadePhase::Display;
+                    fade.current_time = 0.0;
+                    1.0
+                } else {
+                    fade.current_time / fade.fade_in_duration
+                }
+            }
+            FadePhase::Display => {
+                if fade.current_time >= fade.display_duration {
+                    fade.phase = FadePhase::FadeOut;
+                    fade.current_time = 0.0;
+                }
+                1.0
+            }
+            FadePhase::FadeOut => {
+                if fade.current_time >= fade.fade_out_duration {
+                    fade.phase = FadePhase::Complete;
+                    0.0
+                } else {
+                    1.0 - (fade.current_time / fade.fade_out_duration)
+                }
+            }
+            FadePhase::Complete => 0.0,
+        };
+
+        // アルファ値を文字色と背景色（TextSpan の子も含む）に適用
+        let alpha = alpha.clamp(0.0, 1.0);
+        writer.for_each_color(entity, |mut text_color| text_color.0.set_alpha(alpha));
+        for target in std::iter::once(entity).chain(children.into_iter().flatten().copied()) {
+            if let Ok(mut background) = backgrounds.get_mut(target) {
+                background.0.set_alpha(alpha);
+            }
+        }
+
+        // フェードアウト完了時にエンティティを削除リストに追加
+        if fade.phase == FadePhase::Complete {
+            entities_to_remove.push(entity);
+        }
+    }
+
+    // 遅延削除でエンティティを安全に削除
+    for entity in entities_to_remove {
+        match commands.get_entity(entity) {
+            Ok(mut entity_commands) => {
+                entity_commands.despawn();
+            }
+            Err(_) => {
+                // エンティティが既に削除されている場合は無視
+            }
+        }
+    }
+}